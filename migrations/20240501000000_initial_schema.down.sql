@@ -0,0 +1,5 @@
+DROP TABLE available_blocks;
+DROP TABLE players_to_teams;
+DROP TABLE players;
+DROP TABLE teams;
+DROP TABLE users;
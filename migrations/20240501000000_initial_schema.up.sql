@@ -0,0 +1,15 @@
+-- The original schema. IF NOT EXISTS lets databases that were set up by
+-- hand adopt migrations without recreating their tables.
+CREATE TABLE IF NOT EXISTS users(id SERIAL PRIMARY KEY, name varchar(20) UNIQUE not null);
+CREATE TABLE IF NOT EXISTS teams(id SERIAL PRIMARY KEY, name varchar(30) UNIQUE not null);
+CREATE TABLE IF NOT EXISTS players(id SERIAL PRIMARY KEY, user_id int not null REFERENCES users(id));
+CREATE TABLE IF NOT EXISTS players_to_teams(player_id int not null REFERENCES players(id), team_id int not null REFERENCES teams(id), PRIMARY KEY (player_id, team_id));
+
+CREATE TABLE IF NOT EXISTS available_blocks(
+   id SERIAL PRIMARY KEY,
+   start_time time,
+   end_time time,
+   needs_waring boolean,
+   repeats varchar(50), -- rrule
+   player_id int not null REFERENCES players(id)
+);
@@ -0,0 +1,5 @@
+ALTER TABLE available_blocks DROP COLUMN timezone;
+ALTER TABLE available_blocks ALTER COLUMN repeats TYPE varchar(50);
+ALTER TABLE available_blocks ALTER COLUMN need_warning DROP NOT NULL;
+ALTER TABLE available_blocks ALTER COLUMN need_warning DROP DEFAULT;
+ALTER TABLE available_blocks RENAME COLUMN need_warning TO needs_waring;
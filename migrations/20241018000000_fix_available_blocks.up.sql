@@ -0,0 +1,22 @@
+-- Match the column name used by the code, databases set up by hand may have it already
+DO $$
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_schema = current_schema()
+            AND table_name = 'available_blocks'
+            AND column_name = 'needs_waring'
+    ) THEN
+        ALTER TABLE available_blocks RENAME COLUMN needs_waring TO need_warning;
+    END IF;
+END
+$$;
+UPDATE available_blocks SET need_warning = false WHERE need_warning IS NULL;
+ALTER TABLE available_blocks ALTER COLUMN need_warning SET DEFAULT false;
+ALTER TABLE available_blocks ALTER COLUMN need_warning SET NOT NULL;
+
+-- RRULE sets with a DTSTART, EXDATEs and RDATEs don't fit in 50 characters
+ALTER TABLE available_blocks ALTER COLUMN repeats TYPE text;
+
+-- IANA timezone start_time and end_time are given in
+ALTER TABLE available_blocks ADD COLUMN IF NOT EXISTS timezone varchar(64) not null DEFAULT 'UTC';
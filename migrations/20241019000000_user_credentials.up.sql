@@ -0,0 +1,11 @@
+-- Users created before registration existed have no password and can't log in
+ALTER TABLE users ADD COLUMN password_hash text;
+
+-- Only a SHA-256 of each session token is stored, the token itself is handed to the client
+CREATE TABLE sessions(
+    token_hash char(64) PRIMARY KEY,
+    user_id int not null REFERENCES users(id) ON DELETE CASCADE,
+    created_at timestamptz not null default now(),
+    expires_at timestamptz not null
+);
+CREATE INDEX sessions_user_id_idx ON sessions(user_id);
@@ -0,0 +1,4 @@
+ALTER TABLE players_to_teams
+    DROP CONSTRAINT players_to_teams_team_id_fkey,
+    ADD CONSTRAINT players_to_teams_team_id_fkey FOREIGN KEY (team_id) REFERENCES teams(id);
+ALTER TABLE players_to_teams DROP COLUMN role;
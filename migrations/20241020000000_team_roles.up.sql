@@ -0,0 +1,8 @@
+-- Existing memberships become plain members, promote owners and captains by hand
+ALTER TABLE players_to_teams ADD COLUMN role text not null DEFAULT 'member'
+    CHECK (role IN ('owner', 'captain', 'member'));
+
+-- Owners can delete their team, the memberships go with it
+ALTER TABLE players_to_teams
+    DROP CONSTRAINT players_to_teams_team_id_fkey,
+    ADD CONSTRAINT players_to_teams_team_id_fkey FOREIGN KEY (team_id) REFERENCES teams(id) ON DELETE CASCADE;
@@ -0,0 +1,5 @@
+ALTER TABLE teams
+    DROP COLUMN invite_code,
+    DROP COLUMN invite_expires_at,
+    DROP COLUMN invite_max_uses,
+    DROP COLUMN invite_uses;
@@ -0,0 +1,6 @@
+-- One active invite code per team, rotating it replaces the code and resets the use count
+ALTER TABLE teams
+    ADD COLUMN invite_code varchar(16) UNIQUE,
+    ADD COLUMN invite_expires_at timestamptz,
+    ADD COLUMN invite_max_uses int CHECK (invite_max_uses > 0),
+    ADD COLUMN invite_uses int not null DEFAULT 0;
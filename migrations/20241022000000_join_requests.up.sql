@@ -0,0 +1,12 @@
+CREATE TABLE join_requests(
+    id SERIAL PRIMARY KEY,
+    team_id int not null REFERENCES teams(id) ON DELETE CASCADE,
+    player_id int not null REFERENCES players(id) ON DELETE CASCADE,
+    status text not null DEFAULT 'pending' CHECK (status IN ('pending', 'approved', 'rejected')),
+    message varchar(500),
+    created_at timestamptz not null DEFAULT now(),
+    resolved_at timestamptz,
+    resolved_by int REFERENCES users(id) ON DELETE SET NULL
+);
+-- A player can only have one open request per team, resolved ones are kept as history
+CREATE UNIQUE INDEX join_requests_pending_key ON join_requests(team_id, player_id) WHERE status = 'pending';
@@ -0,0 +1,3 @@
+-- Secret token for the team's subscribable calendar feed, rotating it breaks old subscriptions
+ALTER TABLE teams
+    ADD COLUMN feed_token char(64) UNIQUE;
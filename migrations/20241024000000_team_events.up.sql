@@ -0,0 +1,23 @@
+-- Practices and matches a team has scheduled, repeats is an rrule whose DTSTART is starts_at
+CREATE TABLE team_events(
+    id SERIAL PRIMARY KEY,
+    team_id int not null REFERENCES teams(id) ON DELETE CASCADE,
+    title varchar(100) not null,
+    starts_at timestamptz not null,
+    ends_at timestamptz not null CHECK (ends_at > starts_at),
+    repeats text,
+    location varchar(200),
+    notes text,
+    created_by int REFERENCES users(id) ON DELETE SET NULL,
+    created_at timestamptz not null DEFAULT now()
+);
+CREATE INDEX team_events_team_id_idx ON team_events(team_id);
+
+-- One answer per player and event, for every occurrence of a repeating event
+CREATE TABLE event_rsvps(
+    event_id int not null REFERENCES team_events(id) ON DELETE CASCADE,
+    player_id int not null REFERENCES players(id) ON DELETE CASCADE,
+    response text not null CHECK (response IN ('yes', 'no', 'maybe')),
+    updated_at timestamptz not null DEFAULT now(),
+    PRIMARY KEY (event_id, player_id)
+);
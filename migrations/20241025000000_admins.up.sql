@@ -0,0 +1,5 @@
+-- Users allowed to call the /api/admin routes, granted with the `admin grant` command
+CREATE TABLE admins(
+    user_id int PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    granted_at timestamptz not null DEFAULT now()
+);
@@ -0,0 +1,3 @@
+-- Secret token for the player's subscribable calendar feed, rotating it breaks old subscriptions
+ALTER TABLE players
+    ADD COLUMN feed_token char(64) UNIQUE;
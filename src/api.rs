@@ -1,27 +1,91 @@
+use crate::auth::{self, CurrentUser};
+use crate::availability;
+use crate::ical;
+use crate::metrics;
+use crate::import::{self, ImportResult, WakingHours};
+use crate::scheduling::{self, SlotCriteria};
 use crate::data::AvailablityStore;
+use crate::error::Error;
+use crate::extract::{Json, Path, Query};
 use crate::model::*;
+use crate::policy::Policy;
 use axum::{
-    body::Body,
-    extract::{Path, State},
-    http::Response,
-    response::{ErrorResponse, IntoResponse},
-    routing::{get, post},
-    Json, Router,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{AppendHeaders, IntoResponse},
+    routing::{get, patch, post, put},
+    Router,
 };
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use serde::Deserialize;
 use std::sync::Arc;
 
 pub type DynAvailStore = Arc<dyn AvailablityStore + Send + Sync>;
 
 //TODO: Check if team names should be unique
 pub fn api_routes(store: DynAvailStore) -> Router {
-    //TODO: Authentication
     Router::new()
+        .route("/auth/register", post(register))
+        .route("/auth/login", post(login))
+        .route("/auth/logout", post(logout))
+        .route("/auth/me", get(get_current_user))
         .route("/team/create", post(create_team))
+        .route("/team/join/:code", post(join_team_with_invite))
         .route("/team/by-name/:name", get(get_team))
         .route(
             "/team/by-id/:id",
             get(get_team_by_id).patch(update_team).delete(delete_team),
         )
+        .route("/team/by-id/:id/roster", get(get_team_roster))
+        .route(
+            "/team/by-id/:id/join-requests",
+            get(get_join_requests).post(create_join_request),
+        )
+        .route(
+            "/team/by-id/:id/join-requests/:request_id",
+            patch(resolve_join_request),
+        )
+        .route(
+            "/team/by-id/:id/invite",
+            get(get_team_invite)
+                .post(rotate_team_invite)
+                .delete(delete_team_invite),
+        )
+        .route(
+            "/team/by-id/:id/feed",
+            get(get_team_feed)
+                .post(rotate_team_feed)
+                .delete(delete_team_feed),
+        )
+        .route("/feeds/team/:file", get(get_team_feed_calendar))
+        .route("/feeds/player/:file", get(get_player_feed_calendar))
+        .route(
+            "/team/by-id/:id/events",
+            get(get_team_events).post(create_team_event),
+        )
+        .route(
+            "/team/by-id/:id/events/:event_id",
+            get(get_team_event)
+                .patch(update_team_event)
+                .delete(delete_team_event),
+        )
+        .route(
+            "/team/by-id/:id/events/:event_id/rsvps",
+            get(get_event_rsvps),
+        )
+        .route(
+            "/team/by-id/:id/events/:event_id/rsvp",
+            put(set_event_rsvp).delete(delete_event_rsvp),
+        )
+        .route("/team/by-id/:id/availability", get(get_team_availability))
+        .route("/team/by-id/:id/freebusy.ics", get(get_team_freebusy))
+        .route("/team/by-id/:id/suggestions", get(get_team_slot_suggestions))
+        .route(
+            "/team/by-id/:id/roster/:player_id",
+            put(add_player_to_team)
+                .patch(update_team_role)
+                .delete(remove_player_from_team),
+        )
         .route("/user/create", post(create_user))
         .route("/user/by-name/:name", get(get_user))
         .route(
@@ -30,251 +94,1534 @@ pub fn api_routes(store: DynAvailStore) -> Router {
         )
         .route("/player/create", post(create_player))
         .route("/player/by-user-id/:user_id", get(get_player_by_user_id))
-        //TODO: What is this endpoint for? It's for adding a player to a team, should maybe be team/add-player
-        // .route("/player/to-team", get(root))
         .route(
             "/player/:id",
             get(get_player_by_id)
                 .patch(update_player)
                 .delete(delete_player),
         )
+        .route("/player/:id/teams", get(get_teams_by_player))
+        .route(
+            "/player/:id/feed",
+            get(get_player_feed)
+                .post(rotate_player_feed)
+                .delete(delete_player_feed),
+        )
         .route(
             "/available-blocks/create",
             post(create_available_block),
         )
+        .route("/available-blocks/preview", post(preview_available_block))
+        .route("/available-blocks/import", post(import_available_blocks))
         .route("/available-blocks/by-player/:id", get(get_available_blocks_by_player))
+        .route(
+            "/available-blocks/by-player/:id/calendar.ics",
+            get(get_available_blocks_calendar),
+        )
+        .route(
+            "/available-blocks/by-player/:id/freebusy.ics",
+            get(get_player_freebusy),
+        )
         .route(
             "/available-blocks/by-id/:id",
             get(get_available_block_by_id)
                 .patch(update_available_block)
                 .delete(delete_available_block),
         )
+        .route(
+            "/available-blocks/by-id/:id/occurrences",
+            get(get_available_block_occurrences),
+        )
+        .route(
+            "/admin/available-blocks/invalid",
+            get(get_invalid_available_blocks),
+        )
         //TODO: Implement Route
         .with_state(store)
 }
-pub type Result<T, E = ErrorResponse> = core::result::Result<T, E>;
+pub type Result<T, E = Error> = core::result::Result<T, E>;
+
+// Probes for the orchestrator and the Prometheus scrape, served at the root instead of under /api
+pub fn health_routes(store: DynAvailStore) -> Router {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/version", get(version))
+        .route("/metrics", get(get_metrics))
+        .with_state(store)
+}
+
+// How long readiness waits on the store, a probe has to answer before its own timeout
+const READY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+// The process is up, the database isn't checked
+async fn healthz() -> StatusCode {
+    StatusCode::OK
+}
+
+// 503 while the store can't run a query
+async fn readyz(State(store): State<DynAvailStore>) -> impl IntoResponse {
+    let error = match tokio::time::timeout(READY_TIMEOUT, store.ping()).await {
+        Ok(Ok(())) => None,
+        // Probes are unauthenticated, the error can name the host or role so it only goes to the log
+        Ok(Err(err)) => {
+            tracing::warn!("readiness check failed: {}", err);
+            Some("database unavailable".to_string())
+        }
+        Err(_) => Some(format!("no answer within {}s", READY_TIMEOUT.as_secs())),
+    };
+    let status = if error.is_none() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let readiness = Readiness {
+        ready: error.is_none(),
+        error,
+        pool: store.pool_status(),
+    };
+    (status, Json(readiness))
+}
 
-pub enum MyError {
-    No,
-    Yes,
+async fn get_metrics(State(store): State<DynAvailStore>) -> Result<impl IntoResponse> {
+    let text = metrics::render(store.pool_status()).map_err(|err| Error::Internal(err.to_string()))?;
+    Ok(([(header::CONTENT_TYPE, metrics::CONTENT_TYPE)], text))
 }
-impl IntoResponse for MyError {
-    fn into_response(self) -> axum::response::Response {
-        Response::builder().status(500).body(Body::empty()).unwrap()
+
+async fn version(State(store): State<DynAvailStore>) -> impl IntoResponse {
+    let migration = tokio::time::timeout(READY_TIMEOUT, store.applied_migration())
+        .await
+        .ok()
+        .and_then(|result| result.ok())
+        .flatten();
+    Json(VersionInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        git_sha: env!("GIT_SHA").to_string(),
+        migration,
+    })
+}
+
+// Column sizes from the schema migrations
+const MAX_USER_NAME_LEN: usize = 20;
+const MAX_TEAM_NAME_LEN: usize = 30;
+const MAX_JOIN_REQUEST_MESSAGE_LEN: usize = 500;
+const MAX_EVENT_TITLE_LEN: usize = 100;
+const MAX_EVENT_LOCATION_LEN: usize = 200;
+const MIN_PASSWORD_LEN: usize = 8;
+const MAX_PASSWORD_LEN: usize = 128;
+
+fn validate_name(name: &str, max_len: usize) -> Result<()> {
+    if name.trim().is_empty() {
+        return Err(Error::Validation("name must not be empty".to_string()));
+    }
+    if name.chars().count() > max_len {
+        return Err(Error::Validation(format!(
+            "name must be at most {} characters",
+            max_len
+        )));
     }
+    // Names end up in iCalendar lines and log output
+    if name.chars().any(char::is_control) {
+        return Err(Error::Validation(
+            "name must not contain control characters".to_string(),
+        ));
+    }
+    Ok(())
 }
-impl From<sqlx::error::Error> for MyError {
-    fn from(value: sqlx::error::Error) -> Self {
-        MyError::Yes
+
+fn validate_password(password: &str) -> Result<()> {
+    let len = password.chars().count();
+    if !(MIN_PASSWORD_LEN..=MAX_PASSWORD_LEN).contains(&len) {
+        return Err(Error::Validation(format!(
+            "password must be between {} and {} characters",
+            MIN_PASSWORD_LEN, MAX_PASSWORD_LEN
+        )));
+    }
+    Ok(())
+}
+
+fn validate_block(block: &AvailableBlock) -> Result<()> {
+    if block.start_time == block.end_time {
+        return Err(Error::Validation(
+            "startTime and endTime must differ, use an endTime before startTime for blocks that end the next day".to_string(),
+        ));
     }
+    Ok(())
+}
+
+fn validate_event(event: &TeamEvent) -> Result<()> {
+    if event.title.trim().is_empty() {
+        return Err(Error::Validation("title must not be empty".to_string()));
+    }
+    if event.title.chars().count() > MAX_EVENT_TITLE_LEN {
+        return Err(Error::Validation(format!(
+            "title must be at most {} characters",
+            MAX_EVENT_TITLE_LEN
+        )));
+    }
+    if event
+        .location
+        .as_ref()
+        .is_some_and(|location| location.chars().count() > MAX_EVENT_LOCATION_LEN)
+    {
+        return Err(Error::Validation(format!(
+            "location must be at most {} characters",
+            MAX_EVENT_LOCATION_LEN
+        )));
+    }
+    if event.end <= event.start {
+        return Err(Error::Validation("end must be after start".to_string()));
+    }
+    if event
+        .repeats
+        .as_ref()
+        .is_some_and(|repeats| repeats.get_dt_start().with_timezone(&Utc) != event.start)
+    {
+        return Err(Error::Validation(
+            "the DTSTART of repeats must be the event's start".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+const DEFAULT_WINDOW_DAYS: i64 = 7;
+const MAX_WINDOW_DAYS: i64 = 92;
+
+#[derive(Deserialize, Debug)]
+pub struct TimeWindow {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl TimeWindow {
+    // Defaults to the next week starting now
+    fn resolve(&self) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+        let from = self.from.unwrap_or_else(Utc::now);
+        let to = self.to.unwrap_or(from + Duration::days(DEFAULT_WINDOW_DAYS));
+        if to <= from {
+            return Err(Error::Validation("`to` must be after `from`".to_string()));
+        }
+        if to - from > Duration::days(MAX_WINDOW_DAYS) {
+            return Err(Error::Validation(format!(
+                "window must be at most {} days",
+                MAX_WINDOW_DAYS
+            )));
+        }
+        Ok((from, to))
+    }
+}
+
+// Starts a session, the token is returned in the body and as a cookie
+async fn start_session(store: &DynAvailStore, user: IdentifiableUser) -> Result<impl IntoResponse> {
+    let (token, session) = auth::new_session(user.id);
+    let session = store.add_session(session).await?;
+    Ok((
+        AppendHeaders([(header::SET_COOKIE, auth::session_cookie(&token))]),
+        Json(AuthSession {
+            token,
+            expires_at: session.expires_at,
+            user,
+        }),
+    ))
+}
+
+async fn register(
+    State(store): State<DynAvailStore>,
+    Json(data): Json<Credentials>,
+) -> Result<impl IntoResponse> {
+    validate_name(&data.name, MAX_USER_NAME_LEN)?;
+    validate_password(&data.password)?;
+    let password_hash = auth::hash_password(data.password).await?;
+    let user = store
+        .add_user_with_password(User { name: data.name }, password_hash)
+        .await?;
+    start_session(&store, user).await
+}
+
+async fn login(
+    State(store): State<DynAvailStore>,
+    Json(data): Json<Credentials>,
+) -> Result<impl IntoResponse> {
+    // Same answer for an unknown name and a wrong password, and the same time: without a
+    // stored hash the password is still checked, against the dummy one
+    let invalid = || Error::Unauthorized("invalid name or password".to_string());
+    let credentials = store.get_user_credentials_by_name(data.name).await?;
+    let password_hash = credentials
+        .as_ref()
+        .and_then(|credentials| credentials.password_hash.clone());
+    let has_hash = password_hash.is_some();
+    let verified = auth::verify_password(
+        data.password,
+        password_hash.unwrap_or_else(|| auth::DUMMY_PASSWORD_HASH.to_string()),
+    )
+    .await?;
+    let (Some(credentials), true, true) = (credentials, has_hash, verified) else {
+        return Err(invalid());
+    };
+    let user = IdentifiableUser {
+        id: credentials.id,
+        name: credentials.name,
+    };
+    start_session(&store, user).await
+}
+
+async fn logout(
+    State(store): State<DynAvailStore>,
+    _user: CurrentUser,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse> {
+    if let Some(token) = auth::session_token(&headers) {
+        store.delete_session(auth::hash_token(&token)).await?;
+    }
+    Ok((
+        StatusCode::NO_CONTENT,
+        AppendHeaders([(header::SET_COOKIE, auth::clear_session_cookie())]),
+    ))
+}
+
+async fn get_current_user(CurrentUser(user): CurrentUser) -> Result<impl IntoResponse> {
+    Ok(Json(user))
 }
 
 async fn get_team(
     State(store): State<DynAvailStore>,
     Path(data): Path<Team>,
-) -> Result<impl IntoResponse, MyError> {
+) -> Result<impl IntoResponse> {
     if let Some(team) = store.get_team_by_name(data.name).await? {
-        println!("{:?}", team);
         Ok(Json(team))
     } else {
-        //TODO: Return ERROR CODE 404
-        Err(MyError::Yes)
+        Err(Error::NotFound("team"))
     }
-    //TODO: Proper error handling
 }
 
 async fn get_team_by_id(
     State(store): State<DynAvailStore>,
     Path(id): Path<i32>,
-) -> Result<impl IntoResponse, MyError> {
+) -> Result<impl IntoResponse> {
     if let Some(team) = store.get_team_by_id(id).await? {
-        println!("{:?}", team);
         Ok(Json(team))
     } else {
-        //TODO: Return ERROR CODE 404
-        Err(MyError::Yes)
+        Err(Error::NotFound("team"))
     }
 }
 
+// The creator's player becomes the owner
 async fn create_team(
     State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
     Json(data): Json<Team>,
-) -> Result<impl IntoResponse, MyError> {
-    let team = store.add_team(data).await?;
-    //TODO: Better error handling
+) -> Result<impl IntoResponse> {
+    validate_name(&data.name, MAX_TEAM_NAME_LEN)?;
+    let Some(player) = Policy::new(&store, &user).current_player().await? else {
+        return Err(Error::Validation(
+            "create a player before creating a team".to_string(),
+        ));
+    };
+    let team = store.add_team_with_owner(data, player.id).await?;
     Ok(Json(team))
 }
 
-// TODO: Grab id from path parameter?
+// The id in the path wins over any id in the body
 async fn update_team(
     State(store): State<DynAvailStore>,
-    Json(data): Json<IdentifiableTeam>,
-) -> Result<impl IntoResponse, MyError> {
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+    Json(mut data): Json<IdentifiableTeam>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_team_role(id, TeamRole::Captain)
+        .await?;
+    data.id = id;
+    validate_name(&data.name, MAX_TEAM_NAME_LEN)?;
     let team = store.update_team(data).await?;
     Ok(Json(team))
 }
 
 async fn delete_team(
     State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
     Path(id): Path<i32>,
-) -> Result<impl IntoResponse, MyError> {
-    let team = store.delete_team(id).await?;
-    Ok(Json(team))
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_team_role(id, TeamRole::Owner)
+        .await?;
+    store.delete_team(id).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 async fn get_user(
     State(store): State<DynAvailStore>,
     Path(data): Path<User>,
-) -> Result<impl IntoResponse, MyError> {
+) -> Result<impl IntoResponse> {
     if let Some(user) = store.get_user_by_name(data.name).await? {
-        println!("{:?}", user);
         Ok(Json(user))
     } else {
-        //TODO: Return ERROR CODE 404
-        Err(MyError::Yes)
+        Err(Error::NotFound("user"))
     }
-    //TODO: Proper error handling
 }
 
 async fn get_user_by_id(
     State(store): State<DynAvailStore>,
     Path(id): Path<i32>,
-) -> Result<impl IntoResponse, MyError> {
+) -> Result<impl IntoResponse> {
     if let Some(user) = store.get_user_by_id(id).await? {
-        println!("{:?}", user);
         Ok(Json(user))
     } else {
-        //TODO: Return ERROR CODE 404
-        Err(MyError::Yes)
+        Err(Error::NotFound("user"))
     }
 }
 
+// Users without a password can't log in, only admins create them, everyone else registers
 async fn create_user(
     State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
     Json(data): Json<User>,
-) -> Result<impl IntoResponse, MyError> {
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user).require_admin().await?;
+    validate_name(&data.name, MAX_USER_NAME_LEN)?;
     let user = store.add_user(data).await?;
-    //TODO: Better error handling
     Ok(Json(user))
 }
 
 async fn update_user(
     State(store): State<DynAvailStore>,
-    Json(data): Json<IdentifiableUser>,
-) -> Result<impl IntoResponse, MyError> {
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+    Json(mut data): Json<IdentifiableUser>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user).require_self(id)?;
+    data.id = id;
+    validate_name(&data.name, MAX_USER_NAME_LEN)?;
     let user = store.update_user(data).await?;
     Ok(Json(user))
 }
 
 async fn delete_user(
     State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
     Path(id): Path<i32>,
-) -> Result<impl IntoResponse, MyError> {
-    let user = store.delete_user(id).await?;
-    Ok(Json(user))
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user).require_self(id)?;
+    store.delete_user(id).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 async fn get_player_by_user_id(
     State(store): State<DynAvailStore>,
     Path(data): Path<Player>,
-) -> Result<impl IntoResponse, MyError> {
+) -> Result<impl IntoResponse> {
     if let Some(player) = store.get_player_by_user_id(data.user_id).await? {
-        println!("{:?}", player);
         Ok(Json(player))
     } else {
-        //TODO: Return ERROR CODE 404
-        Err(MyError::Yes)
+        Err(Error::NotFound("player"))
     }
 }
 
 async fn get_player_by_id(
     State(store): State<DynAvailStore>,
     Path(id): Path<i32>,
-) -> Result<impl IntoResponse, MyError> {
+) -> Result<impl IntoResponse> {
     if let Some(player) = store.get_player_by_id(id).await? {
-        println!("{:?}", player);
         Ok(Json(player))
     } else {
-        //TODO: Return ERROR CODE 404
-        Err(MyError::Yes)
+        Err(Error::NotFound("player"))
     }
 }
 
 async fn create_player(
     State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
     Json(data): Json<Player>,
-) -> Result<impl IntoResponse, MyError> {
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user).require_self(data.user_id)?;
     let player = store.add_player(data).await?;
-    //TODO: Better error handling
     Ok(Json(player))
 }
 
 async fn update_player(
     State(store): State<DynAvailStore>,
-    Json(data): Json<IdentifiablePlayer>,
-) -> Result<impl IntoResponse, MyError> {
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+    Json(mut data): Json<IdentifiablePlayer>,
+) -> Result<impl IntoResponse> {
+    let policy = Policy::new(&store, &user);
+    policy.require_own_player(id).await?;
+    policy.require_self(data.user_id)?;
+    data.id = id;
     let player = store.update_player(data).await?;
     Ok(Json(player))
 }
 
 async fn delete_player(
     State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
     Path(id): Path<i32>,
-) -> Result<impl IntoResponse, MyError> {
-    let player = store.delete_player(id).await?;
-    Ok(Json(player))
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user).require_own_player(id).await?;
+    store.delete_player(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+async fn get_team_roster(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_team_role(id, TeamRole::Member)
+        .await?;
+    let roster = store.get_team_roster(id).await?;
+    Ok(Json(roster))
+}
+
+async fn get_team_availability(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+    Query(window): Query<TimeWindow>,
+) -> Result<impl IntoResponse> {
+    let (from, to) = window.resolve()?;
+    Policy::new(&store, &user)
+        .require_team_role(id, TeamRole::Member)
+        .await?;
+    let players = store.get_team_roster(id).await?;
+    let blocks = skip_invalid_blocks(store.get_raw_available_blocks_by_team_id(id).await?);
+    metrics::overlap_computed("availability");
+    Ok(Json(availability::team_availability(
+        id, players, &blocks, from, to,
+    )))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FreeBusyParams {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    // One VFREEBUSY for the team, free only when the whole roster is
+    #[serde(default)]
+    pub aggregate: bool,
+}
+
+// One VFREEBUSY per roster member unless aggregated
+async fn get_team_freebusy(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+    Query(params): Query<FreeBusyParams>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_team_role(id, TeamRole::Member)
+        .await?;
+    let (from, to) = TimeWindow {
+        from: params.from,
+        to: params.to,
+    }
+    .resolve()?;
+    let Some(team) = store.get_team_by_id(id).await? else {
+        return Err(Error::NotFound("team"));
+    };
+    let players = store.get_team_roster(id).await?;
+    let blocks = skip_invalid_blocks(store.get_raw_available_blocks_by_team_id(id).await?);
+
+    let now = Utc::now();
+    let mut calendar = ical::Calendar::new(&format!("{} free/busy", team.name));
+    if params.aggregate {
+        metrics::overlap_computed("freebusy");
+        let availability = availability::team_availability(id, players, &blocks, from, to);
+        ical::freebusy(
+            &mut calendar,
+            &format!("team-{}-freebusy", id),
+            None,
+            from,
+            to,
+            &availability.all_available,
+            now,
+        );
+    } else {
+        let per_player = availability::expand_blocks_by_player(&blocks, from, to);
+        for player in &players {
+            let free = availability::union(
+                per_player
+                    .get(&player.player_id)
+                    .cloned()
+                    .unwrap_or_default(),
+            );
+            ical::freebusy(
+                &mut calendar,
+                &format!("player-{}-freebusy", player.player_id),
+                Some((player.player_id, &player.name)),
+                from,
+                to,
+                &free,
+                now,
+            );
+        }
+    }
+    Ok(ical::IcsResponse(calendar.finish()))
 }
+
+const DEFAULT_SUGGESTION_MINUTES: i64 = 60;
+const DEFAULT_SUGGESTION_LIMIT: usize = 5;
+const MAX_SUGGESTION_LIMIT: usize = 50;
+
+#[derive(Deserialize, Debug)]
+pub struct SuggestionParams {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    // Defaults to the whole roster
+    pub min_players: Option<usize>,
+    // In minutes
+    pub min_duration: Option<i64>,
+    pub limit: Option<usize>,
+}
+
+async fn get_team_slot_suggestions(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+    Query(params): Query<SuggestionParams>,
+) -> Result<impl IntoResponse> {
+    let (from, to) = TimeWindow {
+        from: params.from,
+        to: params.to,
+    }
+    .resolve()?;
+    Policy::new(&store, &user)
+        .require_team_role(id, TeamRole::Member)
+        .await?;
+    let players = store.get_team_roster(id).await?;
+    let min_players = params.min_players.unwrap_or(players.len());
+    if min_players == 0 || min_players > players.len() {
+        return Err(Error::Validation(format!(
+            "min_players must be between 1 and the roster size ({})",
+            players.len()
+        )));
+    }
+    let min_duration = params.min_duration.unwrap_or(DEFAULT_SUGGESTION_MINUTES);
+    if min_duration <= 0 {
+        return Err(Error::Validation(
+            "min_duration must be a positive number of minutes".to_string(),
+        ));
+    }
+    let limit = params.limit.unwrap_or(DEFAULT_SUGGESTION_LIMIT);
+    if limit == 0 || limit > MAX_SUGGESTION_LIMIT {
+        return Err(Error::Validation(format!(
+            "limit must be between 1 and {}",
+            MAX_SUGGESTION_LIMIT
+        )));
+    }
+
+    let blocks = skip_invalid_blocks(store.get_raw_available_blocks_by_team_id(id).await?);
+    let criteria = SlotCriteria {
+        from,
+        to,
+        min_players,
+        min_duration: Duration::minutes(min_duration),
+        limit,
+    };
+    metrics::overlap_computed("suggestions");
+    Ok(Json(scheduling::suggest_slots(&players, &blocks, &criteria)))
+}
+
+// New members always join as plain members
+async fn add_player_to_team(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path((team_id, player_id)): Path<(i32, i32)>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_team_role(team_id, TeamRole::Captain)
+        .await?;
+    if store.get_player_by_id(player_id).await?.is_none() {
+        return Err(Error::NotFound("player"));
+    }
+    let membership = store
+        .add_player_to_team(PlayerToTeam {
+            player_id,
+            team_id,
+            role: TeamRole::Member,
+        })
+        .await?;
+    Ok(Json(membership))
+}
+
+// Only the owner hands out roles, ownership itself can't be given away
+async fn update_team_role(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path((team_id, player_id)): Path<(i32, i32)>,
+    Json(data): Json<RoleChange>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_team_role(team_id, TeamRole::Owner)
+        .await?;
+    if data.role == TeamRole::Owner {
+        return Err(Error::Validation(
+            "a team can only have one owner".to_string(),
+        ));
+    }
+    let membership = store
+        .get_team_membership(team_id, player_id)
+        .await?
+        .ok_or(Error::NotFound("roster member"))?;
+    if membership.role == TeamRole::Owner {
+        return Err(Error::Validation(
+            "the owner's role can't be changed".to_string(),
+        ));
+    }
+    let membership = store
+        .update_team_role(PlayerToTeam {
+            role: data.role,
+            ..membership
+        })
+        .await?;
+    Ok(Json(membership))
+}
+
+async fn remove_player_from_team(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path((team_id, player_id)): Path<(i32, i32)>,
+) -> Result<impl IntoResponse> {
+    if store.get_team_by_id(team_id).await?.is_none() {
+        return Err(Error::NotFound("team"));
+    }
+    let membership = store
+        .get_team_membership(team_id, player_id)
+        .await?
+        .ok_or(Error::NotFound("roster member"))?;
+    Policy::new(&store, &user)
+        .require_can_remove(&membership)
+        .await?;
+    match store.remove_player_from_team(membership).await {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(sqlx::Error::RowNotFound) => Err(Error::NotFound("roster member")),
+        Err(err) => Err(err.into()),
+    }
+}
+
+async fn get_team_invite(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_team_role(id, TeamRole::Captain)
+        .await?;
+    if let Some(invite) = store.get_team_invite(id).await? {
+        Ok(Json(invite))
+    } else {
+        Err(Error::NotFound("invite"))
+    }
+}
+
+// Replaces any existing code, links using the old one stop working
+async fn rotate_team_invite(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+    Json(data): Json<InviteOptions>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_team_role(id, TeamRole::Captain)
+        .await?;
+    if data.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+        return Err(Error::Validation(
+            "expires_at must be in the future".to_string(),
+        ));
+    }
+    if data.max_uses.is_some_and(|max_uses| max_uses < 1) {
+        return Err(Error::Validation("max_uses must be at least 1".to_string()));
+    }
+    let invite = store
+        .set_team_invite(TeamInvite {
+            team_id: id,
+            code: auth::new_invite_code(),
+            expires_at: data.expires_at,
+            max_uses: data.max_uses,
+            uses: 0,
+        })
+        .await?;
+    Ok(Json(invite))
+}
+
+async fn delete_team_invite(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_team_role(id, TeamRole::Captain)
+        .await?;
+    store.delete_team_invite(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_team_feed(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_team_role(id, TeamRole::Member)
+        .await?;
+    if let Some(feed) = store.get_team_feed(id).await? {
+        Ok(Json(feed))
+    } else {
+        Err(Error::NotFound("feed"))
+    }
+}
+
+// Replaces any existing token, calendars subscribed with the old url stop updating
+async fn rotate_team_feed(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_team_role(id, TeamRole::Captain)
+        .await?;
+    let feed = store
+        .set_team_feed(TeamFeed {
+            team_id: id,
+            token: auth::new_feed_token(),
+        })
+        .await?;
+    Ok(Json(feed))
+}
+
+async fn delete_team_feed(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_team_role(id, TeamRole::Captain)
+        .await?;
+    store.delete_team_feed(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// How much of the team's availability the feed covers around the time it is fetched
+const FEED_PAST_DAYS: i64 = 7;
+const FEED_FUTURE_DAYS: i64 = 56;
+
+// Public, the token in `<token>.ics` is the only credential. Built fresh on every fetch.
+async fn get_team_feed_calendar(
+    State(store): State<DynAvailStore>,
+    Path(file): Path<String>,
+) -> Result<impl IntoResponse> {
+    let Some(token) = file.strip_suffix(".ics") else {
+        return Err(Error::NotFound("feed"));
+    };
+    let feed = store
+        .get_team_feed_by_token(token.to_string())
+        .await?
+        .ok_or(Error::NotFound("feed"))?;
+    let team = store
+        .get_team_by_id(feed.team_id)
+        .await?
+        .ok_or(Error::NotFound("team"))?;
+
+    let now = Utc::now();
+    let players = store.get_team_roster(team.id).await?;
+    let blocks = skip_invalid_blocks(store.get_raw_available_blocks_by_team_id(team.id).await?);
+    metrics::overlap_computed("feed");
+    let availability = availability::team_availability(
+        team.id,
+        players,
+        &blocks,
+        now - Duration::days(FEED_PAST_DAYS),
+        now + Duration::days(FEED_FUTURE_DAYS),
+    );
+    let mut calendar = ical::Calendar::new(&format!("{} availability", team.name));
+    for interval in &availability.all_available {
+        ical::overlap_event(&mut calendar, team.id, interval, now);
+    }
+    Ok(ical::IcsResponse(calendar.finish()))
+}
+
+// Adds the current user's player to the team as a member
+async fn join_team_with_invite(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(code): Path<String>,
+) -> Result<impl IntoResponse> {
+    let Some(player) = Policy::new(&store, &user).current_player().await? else {
+        return Err(Error::Validation(
+            "create a player before joining a team".to_string(),
+        ));
+    };
+    let code = code.trim().to_uppercase();
+    let invite = store
+        .get_team_invite_by_code(code.clone())
+        .await?
+        .ok_or(Error::NotFound("invite"))?;
+    let now = Utc::now();
+    if invite.is_expired(now) {
+        return Err(Error::Conflict("invite has expired".to_string()));
+    }
+    if invite.is_used_up() {
+        return Err(Error::Conflict("invite has no uses left".to_string()));
+    }
+    match store.redeem_team_invite(code, player.id, now).await? {
+        Some(membership) => Ok(Json(membership)),
+        // Rotated, expired or used up since it was looked up
+        None => Err(Error::Conflict("invite is no longer valid".to_string())),
+    }
+}
+
+async fn create_join_request(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+    Json(data): Json<NewJoinRequest>,
+) -> Result<impl IntoResponse> {
+    let Some(player) = Policy::new(&store, &user).current_player().await? else {
+        return Err(Error::Validation(
+            "create a player before asking to join a team".to_string(),
+        ));
+    };
+    if data
+        .message
+        .as_ref()
+        .is_some_and(|message| message.chars().count() > MAX_JOIN_REQUEST_MESSAGE_LEN)
+    {
+        return Err(Error::Validation(format!(
+            "message must be at most {} characters",
+            MAX_JOIN_REQUEST_MESSAGE_LEN
+        )));
+    }
+    if store.get_team_by_id(id).await?.is_none() {
+        return Err(Error::NotFound("team"));
+    }
+    if store.get_team_membership(id, player.id).await?.is_some() {
+        return Err(Error::AlreadyExists(
+            "you are already on this team".to_string(),
+        ));
+    }
+    let request = store.add_join_request(id, player.id, data.message).await?;
+    Ok(Json(request))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct JoinRequestParams {
+    pub status: Option<JoinRequestStatus>,
+}
+
+async fn get_join_requests(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+    Query(params): Query<JoinRequestParams>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_team_role(id, TeamRole::Captain)
+        .await?;
+    let requests = store.get_join_requests_by_team_id(id, params.status).await?;
+    Ok(Json(requests))
+}
+
+async fn resolve_join_request(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path((team_id, request_id)): Path<(i32, i32)>,
+    Json(data): Json<JoinRequestResolution>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_team_role(team_id, TeamRole::Captain)
+        .await?;
+    if data.status == JoinRequestStatus::Pending {
+        return Err(Error::Validation(
+            "status must be approved or rejected".to_string(),
+        ));
+    }
+    // A request id from another team is treated as missing
+    if store
+        .get_join_request_by_id(request_id)
+        .await?
+        .filter(|request| request.team_id == team_id)
+        .is_none()
+    {
+        return Err(Error::NotFound("join request"));
+    }
+    match store
+        .resolve_join_request(request_id, data.status, user.id, Utc::now())
+        .await?
+    {
+        Some(request) => Ok(Json(request)),
+        None => Err(Error::Conflict(
+            "join request has already been resolved".to_string(),
+        )),
+    }
+}
+
+// The event when it belongs to the team, 404 otherwise
+async fn team_event(store: &DynAvailStore, team_id: i32, event_id: i32) -> Result<IdentifiableTeamEvent> {
+    store
+        .get_team_event_by_id(event_id)
+        .await?
+        .filter(|event| event.team_id == team_id)
+        .ok_or(Error::NotFound("event"))
+}
+
+// The roster with the blocks of its members that decode. Loaded before an event is written so
+// nothing is left to fail once it is saved, a client retrying after an error would add it twice.
+struct TeamSchedule {
+    players: Vec<RosterMember>,
+    blocks: Vec<IdentifiableAvailableBlock>,
+}
+
+impl TeamSchedule {
+    async fn load(store: &DynAvailStore, team_id: i32) -> Result<TeamSchedule> {
+        Ok(TeamSchedule {
+            players: store.get_team_roster(team_id).await?,
+            blocks: skip_invalid_blocks(store.get_raw_available_blocks_by_team_id(team_id).await?),
+        })
+    }
+
+    // Flags the roster members whose blocks leave the first occurrences of the event uncovered
+    fn report(&self, event: IdentifiableTeamEvent) -> availability::ScheduledTeamEvent {
+        let occurrences = availability::event_occurrences(
+            &event.inner_event,
+            availability::EVENT_COVERAGE_OCCURRENCES,
+        );
+        availability::ScheduledTeamEvent {
+            unavailable: availability::uncovered_members(&occurrences, &self.players, &self.blocks),
+            event,
+        }
+    }
+}
+
+async fn get_team_events(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_team_role(id, TeamRole::Member)
+        .await?;
+    let events = store.get_team_events_by_team_id(id).await?;
+    Ok(Json(events))
+}
+
+async fn create_team_event(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+    Json(data): Json<TeamEvent>,
+) -> Result<impl IntoResponse> {
+    validate_event(&data)?;
+    Policy::new(&store, &user)
+        .require_team_role(id, TeamRole::Captain)
+        .await?;
+    let schedule = TeamSchedule::load(&store, id).await?;
+    let event = store.add_team_event(id, data, user.id).await?;
+    Ok(Json(schedule.report(event)))
+}
+
+async fn get_team_event(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path((id, event_id)): Path<(i32, i32)>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_team_role(id, TeamRole::Member)
+        .await?;
+    Ok(Json(team_event(&store, id, event_id).await?))
+}
+
+// Replaces the details, the answer flags members again since the times may have moved
+async fn update_team_event(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path((id, event_id)): Path<(i32, i32)>,
+    Json(data): Json<TeamEvent>,
+) -> Result<impl IntoResponse> {
+    validate_event(&data)?;
+    Policy::new(&store, &user)
+        .require_team_role(id, TeamRole::Captain)
+        .await?;
+    let mut event = team_event(&store, id, event_id).await?;
+    event.inner_event = data;
+    let schedule = TeamSchedule::load(&store, id).await?;
+    let event = store.update_team_event(event).await?;
+    Ok(Json(schedule.report(event)))
+}
+
+async fn delete_team_event(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path((id, event_id)): Path<(i32, i32)>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_team_role(id, TeamRole::Captain)
+        .await?;
+    team_event(&store, id, event_id).await?;
+    store.delete_team_event(event_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_event_rsvps(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path((id, event_id)): Path<(i32, i32)>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_team_role(id, TeamRole::Member)
+        .await?;
+    team_event(&store, id, event_id).await?;
+    let rsvps = store.get_rsvps_by_event_id(event_id).await?;
+    Ok(Json(rsvps))
+}
+
+// Answers for the current user's player, which has to be on the roster
+async fn set_event_rsvp(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path((id, event_id)): Path<(i32, i32)>,
+    Json(data): Json<RsvpChange>,
+) -> Result<impl IntoResponse> {
+    let policy = Policy::new(&store, &user);
+    policy.require_team_role(id, TeamRole::Member).await?;
+    let player = policy
+        .current_player()
+        .await?
+        .ok_or(Error::NotFound("player"))?;
+    team_event(&store, id, event_id).await?;
+    let rsvp = store
+        .set_rsvp(event_id, player.id, data.response, Utc::now())
+        .await?;
+    Ok(Json(rsvp))
+}
+
+async fn delete_event_rsvp(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path((id, event_id)): Path<(i32, i32)>,
+) -> Result<impl IntoResponse> {
+    let policy = Policy::new(&store, &user);
+    policy.require_team_role(id, TeamRole::Member).await?;
+    let player = policy
+        .current_player()
+        .await?
+        .ok_or(Error::NotFound("player"))?;
+    team_event(&store, id, event_id).await?;
+    store.delete_rsvp(event_id, player.id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_teams_by_player(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user).require_player_viewer(id).await?;
+    let teams = store.get_teams_by_player_id(id).await?;
+    Ok(Json(teams))
+}
+
 //TODO: Check types on all path params
-//TODO: Update to return Vec of Blocks for a player
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OnInvalid {
+    // Fail the whole request if any stored rule doesn't parse
+    #[default]
+    Fail,
+    // Leave out blocks whose stored rule doesn't parse
+    Skip,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BlockListParams {
+    #[serde(default)]
+    pub on_invalid: OnInvalid,
+}
+
 async fn get_available_blocks_by_player(
     State(store): State<DynAvailStore>,
-    Path(data): Path<Player>,
-) -> Result<impl IntoResponse, MyError> {
-    //TODO: Fix this method, might error if len(blocks) = 0
-        let blocks = store.get_available_blocks_by_player_id(data.user_id).await?;
-        println!("{:?}", blocks[0].inner_block);
-        Ok(Json(blocks))
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+    Query(params): Query<BlockListParams>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user).require_player_viewer(id).await?;
+    let blocks = match params.on_invalid {
+        OnInvalid::Fail => store.get_available_blocks_by_player_id(id).await?,
+        OnInvalid::Skip => {
+            skip_invalid_blocks(store.get_raw_available_blocks_by_player_id(id).await?)
+        }
+    };
+    Ok(Json(blocks))
+}
+
+// Leaves out the blocks that don't decode with a warning, so one bad row among the blocks of a
+// whole team doesn't fail the read
+fn skip_invalid_blocks(raws: Vec<RawAvailableBlock>) -> Vec<IdentifiableAvailableBlock> {
+    raws.into_iter()
+        .filter_map(|raw| {
+            let block_id = raw.id;
+            IdentifiableAvailableBlock::try_from(raw)
+                .map_err(|err| tracing::warn!("skipping available block {}: {}", block_id, err))
+                .ok()
+        })
+        .collect()
+}
+
+async fn get_available_blocks_calendar(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user).require_player_viewer(id).await?;
+    player_calendar(&store, id).await
+}
+
+// Calendar apps poll this unattended, so blocks that don't parse are left out rather than
+// failing the whole feed
+async fn player_calendar(store: &DynAvailStore, player_id: i32) -> Result<ical::IcsResponse> {
+    let name = player_name(store, player_id).await?;
+    let blocks =
+        skip_invalid_blocks(store.get_raw_available_blocks_by_player_id(player_id).await?);
+
+    let now = Utc::now();
+    let mut calendar = ical::Calendar::new(&format!("{} availability", name));
+    for block in &blocks {
+        ical::block_event(&mut calendar, block, now);
+    }
+    Ok(ical::IcsResponse(calendar.finish()))
+}
+
+async fn get_player_feed(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user).require_own_player(id).await?;
+    if let Some(feed) = store.get_player_feed(id).await? {
+        Ok(Json(feed))
+    } else {
+        Err(Error::NotFound("feed"))
+    }
+}
+
+// Replaces any existing token, calendars subscribed with the old url stop updating
+async fn rotate_player_feed(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user).require_own_player(id).await?;
+    let feed = store
+        .set_player_feed(PlayerFeed {
+            player_id: id,
+            token: auth::new_feed_token(),
+        })
+        .await?;
+    Ok(Json(feed))
+}
+
+async fn delete_player_feed(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user).require_own_player(id).await?;
+    store.delete_player_feed(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Public, the token in `<token>.ics` is the only credential
+async fn get_player_feed_calendar(
+    State(store): State<DynAvailStore>,
+    Path(file): Path<String>,
+) -> Result<impl IntoResponse> {
+    let Some(token) = file.strip_suffix(".ics") else {
+        return Err(Error::NotFound("feed"));
+    };
+    let feed = store
+        .get_player_feed_by_token(token.to_string())
+        .await?
+        .ok_or(Error::NotFound("feed"))?;
+    player_calendar(&store, feed.player_id).await
+}
+
+// The name of the player's user for calendar titles, 404 when the player doesn't exist
+async fn player_name(store: &DynAvailStore, player_id: i32) -> Result<String> {
+    let Some(player) = store.get_player_by_id(player_id).await? else {
+        return Err(Error::NotFound("player"));
+    };
+    Ok(match store.get_user_by_id(player.user_id).await? {
+        Some(user) => user.name,
+        None => format!("Player {}", player_id),
+    })
+}
+
+async fn get_player_freebusy(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+    Query(window): Query<TimeWindow>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user).require_player_viewer(id).await?;
+    let (from, to) = window.resolve()?;
+    let name = player_name(&store, id).await?;
+    let blocks = skip_invalid_blocks(store.get_raw_available_blocks_by_player_id(id).await?);
+    let free = availability::union(
+        blocks
+            .iter()
+            .flat_map(|block| availability::expand_block(&block.inner_block, from, to))
+            .collect(),
+    );
+
+    let mut calendar = ical::Calendar::new(&format!("{} free/busy", name));
+    ical::freebusy(
+        &mut calendar,
+        &format!("player-{}-freebusy", id),
+        Some((id, &name)),
+        from,
+        to,
+        &free,
+        Utc::now(),
+    );
+    Ok(ical::IcsResponse(calendar.finish()))
+}
+
+// Reports every stored block with a NULL time or rule, or whose rule or timezone doesn't
+// parse, so it can be fixed by hand
+async fn get_invalid_available_blocks(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user).require_admin().await?;
+    let invalid: Vec<InvalidAvailableBlock> = store
+        .get_raw_available_blocks()
+        .await?
+        .into_iter()
+        .filter_map(|raw| {
+            IdentifiableAvailableBlock::try_from(raw.clone())
+                .err()
+                .map(|err| InvalidAvailableBlock {
+                    id: raw.id,
+                    player_id: raw.player_id,
+                    repeats: raw.repeats,
+                    timezone: raw.timezone,
+                    error: err.to_string(),
+                })
+        })
+        .collect();
+    Ok(Json(invalid))
 }
 
 async fn get_available_block_by_id(
     State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
     Path(id): Path<i32>,
-) -> Result<impl IntoResponse, MyError> {
-    if let Some(block) = store.get_player_by_id(id).await? {
-        println!("{:?}", block);
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_player_viewer(available_block_player_id(&store, id).await?)
+        .await?;
+    if let Some(block) = store.get_available_block_by_id(id).await? {
         Ok(Json(block))
     } else {
-        //TODO: Return ERROR CODE 404
-        Err(MyError::Yes)
+        Err(Error::NotFound("available block"))
+    }
+}
+
+const DEFAULT_OCCURRENCE_LIMIT: usize = 100;
+const MAX_OCCURRENCE_LIMIT: usize = 1000;
+
+#[derive(Deserialize, Debug)]
+pub struct OccurrenceParams {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+impl OccurrenceParams {
+    fn resolve(&self) -> Result<(DateTime<Utc>, DateTime<Utc>, usize)> {
+        let (from, to) = TimeWindow {
+            from: self.from,
+            to: self.to,
+        }
+        .resolve()?;
+        let limit = self.limit.unwrap_or(DEFAULT_OCCURRENCE_LIMIT);
+        if limit == 0 || limit > MAX_OCCURRENCE_LIMIT {
+            return Err(Error::Validation(format!(
+                "limit must be between 1 and {}",
+                MAX_OCCURRENCE_LIMIT
+            )));
+        }
+        Ok((from, to, limit))
+    }
+}
+
+async fn get_available_block_occurrences(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+    Query(params): Query<OccurrenceParams>,
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_player_viewer(available_block_player_id(&store, id).await?)
+        .await?;
+    let (from, to, limit) = params.resolve()?;
+    if let Some(block) = store.get_available_block_by_id(id).await? {
+        Ok(Json(availability::block_occurrences(
+            &block.inner_block,
+            from,
+            to,
+            limit,
+        )))
+    } else {
+        Err(Error::NotFound("available block"))
     }
 }
 
+// Expands a block that hasn't been saved yet so the UI can show what the rule produces
+async fn preview_available_block(
+    Query(params): Query<OccurrenceParams>,
+    Json(data): Json<AvailableBlock>,
+) -> Result<impl IntoResponse> {
+    let (from, to, limit) = params.resolve()?;
+    validate_block(&data)?;
+    Ok(Json(availability::block_occurrences(&data, from, to, limit)))
+}
+
 async fn create_available_block(
     State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
     Json(data): Json<AvailableBlock>,
-) -> Result<impl IntoResponse, MyError> {
+) -> Result<impl IntoResponse> {
+    validate_block(&data)?;
+    Policy::new(&store, &user)
+        .require_block_editor(data.player_id)
+        .await?;
     let block = store.add_available_block(data).await?;
-    //TODO: Better error handling
+    metrics::blocks_created("api", 1);
     Ok(Json(block))
 }
 
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    // Every event becomes a block
+    #[default]
+    Available,
+    // Events are busy time, blocks cover the rest of the waking hours
+    Busy,
+}
+
+const DEFAULT_WAKE_START: NaiveTime = NaiveTime::from_hms_opt(8, 0, 0).unwrap();
+const DEFAULT_WAKE_END: NaiveTime = NaiveTime::from_hms_opt(22, 0, 0).unwrap();
+const MAX_IMPORTED_BLOCKS: usize = 500;
+
+#[derive(Deserialize, Debug)]
+pub struct ImportParams {
+    pub player_id: i32,
+    #[serde(default)]
+    pub mode: ImportMode,
+    #[serde(default)]
+    pub dry_run: bool,
+    // For times without a timezone and for the blocks made from busy time, defaults to UTC
+    pub timezone: Option<chrono_tz::Tz>,
+    pub wake_start: Option<NaiveTime>,
+    pub wake_end: Option<NaiveTime>,
+    // The dates busy time is inverted over
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+// The body is the .ics file itself
+async fn import_available_blocks(
+    State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
+    Query(params): Query<ImportParams>,
+    body: String,
+) -> Result<axum::response::Response> {
+    Policy::new(&store, &user)
+        .require_block_editor(params.player_id)
+        .await?;
+    let events = ical::parse_events(&body).map_err(|err| Error::Validation(err.to_string()))?;
+    let timezone = params.timezone.unwrap_or(chrono_tz::UTC);
+    let (blocks, skipped) = match params.mode {
+        ImportMode::Available => import::blocks_from_events(&events, params.player_id, timezone),
+        ImportMode::Busy => {
+            let (from, to) = TimeWindow {
+                from: params.from,
+                to: params.to,
+            }
+            .resolve()?;
+            let waking = WakingHours {
+                start: params.wake_start.unwrap_or(DEFAULT_WAKE_START),
+                end: params.wake_end.unwrap_or(DEFAULT_WAKE_END),
+            };
+            if waking.start == waking.end {
+                return Err(Error::Validation(
+                    "wake_start and wake_end must differ".to_string(),
+                ));
+            }
+            import::blocks_from_busy_time(&events, params.player_id, timezone, waking, from, to)
+        }
+    };
+    if blocks.len() > MAX_IMPORTED_BLOCKS {
+        return Err(Error::Validation(format!(
+            "import would create {} blocks, at most {} are allowed",
+            blocks.len(),
+            MAX_IMPORTED_BLOCKS
+        )));
+    }
+    if params.dry_run {
+        return Ok(Json(ImportResult {
+            dry_run: true,
+            blocks,
+            skipped,
+        })
+        .into_response());
+    }
+    let blocks = store.add_available_blocks(blocks).await?;
+    metrics::blocks_created("import", blocks.len());
+    Ok(Json(ImportResult {
+        dry_run: false,
+        blocks,
+        skipped,
+    })
+    .into_response())
+}
+
+async fn available_block_player_id(store: &DynAvailStore, block_id: i32) -> Result<i32> {
+    store
+        .get_available_block_player_id(block_id)
+        .await?
+        .ok_or(Error::NotFound("available block"))
+}
+
+// Moving a block to another player needs edit rights on both
 async fn update_available_block(
     State(store): State<DynAvailStore>,
-    Json(data): Json<IdentifiableAvailableBlock>,
-) -> Result<impl IntoResponse, MyError> {
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+    Json(mut data): Json<IdentifiableAvailableBlock>,
+) -> Result<impl IntoResponse> {
+    data.id = id;
+    validate_block(&data.inner_block)?;
+    let policy = Policy::new(&store, &user);
+    policy
+        .require_block_editor(available_block_player_id(&store, id).await?)
+        .await?;
+    policy
+        .require_block_editor(data.inner_block.player_id)
+        .await?;
     let block = store.update_available_block(data).await?;
     Ok(Json(block))
 }
 
 async fn delete_available_block(
     State(store): State<DynAvailStore>,
+    CurrentUser(user): CurrentUser,
     Path(id): Path<i32>,
-) -> Result<impl IntoResponse, MyError> {
-    let block = store.delete_available_block(id).await?;
-    Ok(Json(block))
+) -> Result<impl IntoResponse> {
+    Policy::new(&store, &user)
+        .require_block_editor(available_block_player_id(&store, id).await?)
+        .await?;
+    store.delete_available_block(id).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
-//TODO: REMOVE
-async fn root() -> &'static str {
-    "Hello, World!"
-}
@@ -0,0 +1,149 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{header, request::Parts, HeaderMap},
+};
+use chrono::{Duration, Utc};
+use sha2::{Digest, Sha256};
+
+use crate::api::DynAvailStore;
+use crate::error::Error;
+use crate::model::*;
+
+pub const SESSION_COOKIE: &str = "session";
+pub const SESSION_DAYS: i64 = 30;
+const TOKEN_BYTES: usize = 32;
+// No 0/O or 1/I/L so codes survive being read out loud or retyped from a screenshot
+const INVITE_ALPHABET: &[u8] = b"23456789ABCDEFGHJKMNPQRSTUVWXYZ";
+const INVITE_CODE_LEN: usize = 10;
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Hashes on the blocking pool, argon2 is deliberately slow
+pub async fn hash_password(password: String) -> Result<String, Error> {
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|err| Error::Internal(format!("could not hash password: {}", err)))
+    })
+    .await
+    .map_err(|err| Error::Internal(err.to_string()))?
+}
+
+// An argon2 hash of a random password nobody knows. Logins for unknown names are checked
+// against it so they take as long as a wrong password.
+pub const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$NNXJlHHfoOWSz9K5b+h3XQ$0R+PLUuBuGklai1L+eXJbG7DiX8jh8DYShzbYuOAqDo";
+
+pub async fn verify_password(password: String, password_hash: String) -> Result<bool, Error> {
+    tokio::task::spawn_blocking(move || {
+        let parsed = PasswordHash::new(&password_hash)
+            .map_err(|err| Error::InvalidStoredData(format!("password hash: {}", err)))?;
+        Ok(Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok())
+    })
+    .await
+    .map_err(|err| Error::Internal(err.to_string()))?
+}
+
+pub fn hash_token(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    to_hex(&bytes)
+}
+
+/// Creates a session for the user, returns the plain token with the row to store
+pub fn new_session(user_id: i32) -> (String, Session) {
+    let token = random_token();
+    let created_at = Utc::now();
+    let session = Session {
+        token_hash: hash_token(&token),
+        user_id,
+        created_at,
+        expires_at: created_at + Duration::days(SESSION_DAYS),
+    };
+    (token, session)
+}
+
+pub fn new_invite_code() -> String {
+    (0..INVITE_CODE_LEN)
+        .map(|_| {
+            let index = OsRng.next_u32() as usize % INVITE_ALPHABET.len();
+            INVITE_ALPHABET[index] as char
+        })
+        .collect()
+}
+
+// Calendar apps can't send headers, so the token in the feed url is the only secret
+pub fn new_feed_token() -> String {
+    random_token()
+}
+
+// Secure keeps the cookie off plain http, browsers still send it to http://localhost
+pub fn session_cookie(token: &str) -> String {
+    format!(
+        "{}={}; HttpOnly; Secure; SameSite=Lax; Path=/; Max-Age={}",
+        SESSION_COOKIE,
+        token,
+        Duration::days(SESSION_DAYS).num_seconds()
+    )
+}
+
+pub fn clear_session_cookie() -> String {
+    format!("{}=; HttpOnly; Secure; SameSite=Lax; Path=/; Max-Age=0", SESSION_COOKIE)
+}
+
+/// The token from `Authorization: Bearer`, falling back to the session cookie
+pub fn session_token(headers: &HeaderMap) -> Option<String> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+    bearer.or_else(|| {
+        headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(name, _)| *name == SESSION_COOKIE)
+            .map(|(_, token)| token.to_string())
+    })
+}
+
+/// The logged in user, rejects with 401 when there is no valid session
+#[derive(Debug, Clone)]
+pub struct CurrentUser(pub IdentifiableUser);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CurrentUser
+where
+    DynAvailStore: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let token = session_token(&parts.headers)
+            .ok_or_else(|| Error::Unauthorized("missing session token".to_string()))?;
+        let store = DynAvailStore::from_ref(state);
+        store
+            .get_user_by_session(hash_token(&token), Utc::now())
+            .await?
+            .map(CurrentUser)
+            .ok_or_else(|| Error::Unauthorized("session is invalid or has expired".to_string()))
+    }
+}
@@ -0,0 +1,406 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::model::*;
+
+// Upper bound on occurrences expanded per block, keeps a bad rule from spinning
+const MAX_OCCURRENCES_PER_BLOCK: u16 = 2000;
+// How many occurrences of a repeating event are checked against the roster's blocks
+pub const EVENT_COVERAGE_OCCURRENCES: u16 = 12;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AvailabilityInterval {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub player_ids: Vec<i32>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockOccurrences {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub occurrences: Vec<Interval>,
+    // More occurrences exist in the window than were returned
+    pub limited: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamAvailability {
+    pub team_id: i32,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub players: Vec<RosterMember>,
+    pub intervals: Vec<AvailabilityInterval>,
+    pub all_available: Vec<Interval>,
+}
+
+// Resolves a local wall clock time in the block's timezone. Ambiguous times at the end of
+// DST take the first instant, times skipped at the start of DST move an hour forward.
+pub(crate) fn resolve_local<Tz: TimeZone>(tz: &Tz, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+    tz.from_local_datetime(&local)
+        .earliest()
+        .or_else(|| tz.from_local_datetime(&(local + Duration::hours(1))).earliest())
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// Builds the concrete interval for every occurrence of the block's rule dated within
+/// `[after, before]`. Each occurrence takes its date from the rule, as written in the rule's
+/// own timezone, and its times from `start_time`/`end_time` in the block's timezone.
+/// Overnight blocks end on the day after the occurrence.
+/// The flag is set when the rule produced more occurrences than could be expanded.
+pub fn occurrences(
+    block: &AvailableBlock,
+    after: DateTime<Utc>,
+    before: DateTime<Utc>,
+) -> (Vec<Interval>, bool) {
+    let result = (*block.repeats)
+        .clone()
+        .after(after.with_timezone(&rrule::Tz::UTC))
+        .before(before.with_timezone(&rrule::Tz::UTC))
+        .all(MAX_OCCURRENCES_PER_BLOCK);
+
+    let intervals = result
+        .dates
+        .into_iter()
+        .filter_map(|occurrence| {
+            let date = occurrence.date_naive();
+            let end_date = if block.is_overnight() {
+                date.succ_opt()?
+            } else {
+                date
+            };
+            let start = resolve_local(&block.timezone, date.and_time(block.start_time))?;
+            let end = resolve_local(&block.timezone, end_date.and_time(block.end_time))?;
+            Some(Interval { start, end })
+        })
+        .collect();
+    (intervals, result.limited)
+}
+
+/// Expands a block's recurrence rule into the concrete intervals that intersect `[from, to)`
+pub fn expand_block(block: &AvailableBlock, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<Interval> {
+    // The block's timezone can put an occurrence up to a day either side of its rule date,
+    // and an overnight block from the day before can still run into the window
+    let (intervals, _) = occurrences(block, from - Duration::days(2), to + Duration::days(1));
+    intervals
+        .into_iter()
+        .filter_map(|interval| clip(interval, from, to))
+        .collect()
+}
+
+fn clip(interval: Interval, from: DateTime<Utc>, to: DateTime<Utc>) -> Option<Interval> {
+    let start = interval.start.max(from);
+    let end = interval.end.min(to);
+    if start < end {
+        Some(Interval { start, end })
+    } else {
+        None
+    }
+}
+
+/// Sweeps every player's intervals and returns the segments where at least one player is
+/// free, annotated with who is free. Adjacent segments with the same players are merged.
+pub fn merge_availability(per_player: &BTreeMap<i32, Vec<Interval>>) -> Vec<AvailabilityInterval> {
+    // Per instant, how many of each player's intervals open (+1) or close (-1)
+    let mut events: BTreeMap<DateTime<Utc>, BTreeMap<i32, i32>> = BTreeMap::new();
+    for (player_id, intervals) in per_player {
+        for interval in intervals {
+            if interval.start >= interval.end {
+                continue;
+            }
+            *events
+                .entry(interval.start)
+                .or_default()
+                .entry(*player_id)
+                .or_default() += 1;
+            *events
+                .entry(interval.end)
+                .or_default()
+                .entry(*player_id)
+                .or_default() -= 1;
+        }
+    }
+
+    let mut open: BTreeMap<i32, i32> = BTreeMap::new();
+    let mut result: Vec<AvailabilityInterval> = Vec::new();
+    let mut previous: Option<DateTime<Utc>> = None;
+    for (time, changes) in events {
+        if let Some(segment_start) = previous {
+            let player_ids: Vec<i32> = open
+                .iter()
+                .filter(|(_, count)| **count > 0)
+                .map(|(player_id, _)| *player_id)
+                .collect();
+            if !player_ids.is_empty() {
+                match result.last_mut() {
+                    Some(last) if last.end == segment_start && last.player_ids == player_ids => {
+                        last.end = time;
+                    }
+                    _ => result.push(AvailabilityInterval {
+                        start: segment_start,
+                        end: time,
+                        player_ids,
+                    }),
+                }
+            }
+        }
+        for (player_id, change) in changes {
+            *open.entry(player_id).or_default() += change;
+        }
+        previous = Some(time);
+    }
+    result
+}
+
+/// The segments from `merge_availability` where every player in `roster` is free
+pub fn all_available(intervals: &[AvailabilityInterval], roster: &BTreeSet<i32>) -> Vec<Interval> {
+    if roster.is_empty() {
+        return Vec::new();
+    }
+    let mut result: Vec<Interval> = Vec::new();
+    for interval in intervals {
+        if !roster.iter().all(|id| interval.player_ids.contains(id)) {
+            continue;
+        }
+        match result.last_mut() {
+            Some(last) if last.end == interval.start => last.end = interval.end,
+            _ => result.push(Interval {
+                start: interval.start,
+                end: interval.end,
+            }),
+        }
+    }
+    result
+}
+
+/// Sorts the intervals and joins the ones that overlap or touch
+pub fn union(mut intervals: Vec<Interval>) -> Vec<Interval> {
+    intervals.sort_by_key(|interval| interval.start);
+    let mut result: Vec<Interval> = Vec::new();
+    for interval in intervals {
+        match result.last_mut() {
+            Some(last) if interval.start <= last.end => last.end = last.end.max(interval.end),
+            _ => result.push(interval),
+        }
+    }
+    result
+}
+
+/// The parts of `[from, to)` not covered by `intervals`, which must be sorted and disjoint
+pub fn complement(intervals: &[Interval], from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<Interval> {
+    let mut result = Vec::new();
+    let mut cursor = from;
+    for interval in intervals {
+        if interval.start > cursor {
+            result.push(Interval {
+                start: cursor,
+                end: interval.start.min(to),
+            });
+        }
+        cursor = cursor.max(interval.end);
+        if cursor >= to {
+            return result;
+        }
+    }
+    if cursor < to {
+        result.push(Interval { start: cursor, end: to });
+    }
+    result
+}
+
+/// Expands every block and groups the resulting intervals by player
+pub fn expand_blocks_by_player(
+    blocks: &[IdentifiableAvailableBlock],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> BTreeMap<i32, Vec<Interval>> {
+    let mut per_player: BTreeMap<i32, Vec<Interval>> = BTreeMap::new();
+    for block in blocks {
+        per_player
+            .entry(block.inner_block.player_id)
+            .or_default()
+            .extend(expand_block(&block.inner_block, from, to));
+    }
+    per_player
+}
+
+/// The occurrences of a block that start within `[from, to)`, at most `limit` of them
+pub fn block_occurrences(
+    block: &AvailableBlock,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    limit: usize,
+) -> BlockOccurrences {
+    let (intervals, rule_limited) =
+        occurrences(block, from - Duration::days(1), to + Duration::days(1));
+    let mut occurrences: Vec<Interval> = intervals
+        .into_iter()
+        .filter(|interval| interval.start >= from && interval.start < to)
+        .collect();
+    let limited = rule_limited || occurrences.len() > limit;
+    occurrences.truncate(limit);
+    BlockOccurrences {
+        from,
+        to,
+        occurrences,
+        limited,
+    }
+}
+
+pub fn team_availability(
+    team_id: i32,
+    players: Vec<RosterMember>,
+    blocks: &[IdentifiableAvailableBlock],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> TeamAvailability {
+    let roster: BTreeSet<i32> = players.iter().map(|player| player.player_id).collect();
+    let per_player = expand_blocks_by_player(blocks, from, to);
+    let intervals = merge_availability(&per_player);
+    let all_available = all_available(&intervals, &roster);
+    TeamAvailability {
+        team_id,
+        from,
+        to,
+        players,
+        intervals,
+        all_available,
+    }
+}
+
+// A roster member whose blocks leave some occurrences of an event uncovered
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UncoveredMember {
+    pub player_id: i32,
+    pub name: String,
+    pub missed: Vec<Interval>,
+}
+
+// A newly scheduled event with the members who haven't said they are available for it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduledTeamEvent {
+    #[serde(flatten)]
+    pub event: IdentifiableTeamEvent,
+    pub unavailable: Vec<UncoveredMember>,
+}
+
+/// The event's occurrences, only the first `limit` of a repeating event
+pub fn event_occurrences(event: &TeamEvent, limit: u16) -> Vec<Interval> {
+    let length = event.end - event.start;
+    match &event.repeats {
+        None => vec![Interval {
+            start: event.start,
+            end: event.end,
+        }],
+        Some(repeats) => (**repeats)
+            .clone()
+            .all(limit)
+            .dates
+            .into_iter()
+            .map(|start| {
+                let start = start.with_timezone(&Utc);
+                Interval {
+                    start,
+                    end: start + length,
+                }
+            })
+            .collect(),
+    }
+}
+
+/// The players whose blocks don't cover every one of the occurrences, with the ones they miss
+pub fn uncovered_members(
+    occurrences: &[Interval],
+    players: &[RosterMember],
+    blocks: &[IdentifiableAvailableBlock],
+) -> Vec<UncoveredMember> {
+    let (Some(from), Some(to)) = (
+        occurrences.iter().map(|occurrence| occurrence.start).min(),
+        occurrences.iter().map(|occurrence| occurrence.end).max(),
+    ) else {
+        return Vec::new();
+    };
+    let per_player = expand_blocks_by_player(blocks, from, to);
+    players
+        .iter()
+        .filter_map(|player| {
+            let free = union(per_player.get(&player.player_id).cloned().unwrap_or_default());
+            let missed: Vec<Interval> = occurrences
+                .iter()
+                .filter(|occurrence| {
+                    !free
+                        .iter()
+                        .any(|interval| interval.start <= occurrence.start && interval.end >= occurrence.end)
+                })
+                .copied()
+                .collect();
+            (!missed.is_empty()).then(|| UncoveredMember {
+                player_id: player.player_id,
+                name: player.name.clone(),
+                missed,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 19, hour, 0, 0).unwrap()
+    }
+
+    fn interval(start: u32, end: u32) -> Interval {
+        Interval {
+            start: at(start),
+            end: at(end),
+        }
+    }
+
+    #[test]
+    fn merge_annotates_who_is_free() {
+        let per_player = BTreeMap::from([
+            (1, vec![interval(10, 14)]),
+            // Overlapping intervals of one player count once
+            (2, vec![interval(11, 13), interval(12, 13)]),
+        ]);
+        let merged = merge_availability(&per_player);
+        let segments: Vec<(DateTime<Utc>, DateTime<Utc>, Vec<i32>)> = merged
+            .iter()
+            .map(|segment| (segment.start, segment.end, segment.player_ids.clone()))
+            .collect();
+        assert_eq!(
+            segments,
+            vec![
+                (at(10), at(11), vec![1]),
+                (at(11), at(13), vec![1, 2]),
+                (at(13), at(14), vec![1]),
+            ]
+        );
+        assert_eq!(all_available(&merged, &BTreeSet::from([1, 2])), vec![interval(11, 13)]);
+        assert!(all_available(&merged, &BTreeSet::new()).is_empty());
+    }
+
+    #[test]
+    fn union_and_complement() {
+        let free = union(vec![interval(14, 16), interval(10, 12), interval(12, 13)]);
+        assert_eq!(free, vec![interval(10, 13), interval(14, 16)]);
+        assert_eq!(
+            complement(&free, at(8), at(18)),
+            vec![interval(8, 10), interval(13, 14), interval(16, 18)]
+        );
+        assert_eq!(complement(&free, at(11), at(15)), vec![interval(13, 14)]);
+    }
+}
@@ -0,0 +1,411 @@
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use axum::http::{header, HeaderValue, Method};
+use clap::{Args, ValueEnum};
+use serde::Deserialize;
+use sqlx::postgres::PgPoolOptions;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing_subscriber::EnvFilter;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("can't read config file {path}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid config file {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("{0}")]
+    Invalid(String),
+}
+
+// Settings given as flags or environment variables, they take precedence over the config file
+#[derive(Args, Debug, Default, Clone)]
+pub struct ConfigArgs {
+    /// TOML file to read the settings from
+    #[arg(long = "config", env = "CONFIG_FILE")]
+    pub config_file: Option<PathBuf>,
+
+    /// Address to listen on [default: 0.0.0.0:3000]
+    #[arg(long, env = "BIND_ADDRESS")]
+    pub bind: Option<SocketAddr>,
+
+    /// Seconds in-flight requests get to finish after SIGTERM or SIGINT [default: 30]
+    #[arg(long, env = "SHUTDOWN_TIMEOUT")]
+    pub shutdown_timeout: Option<u64>,
+
+    /// Where the data is kept, `memory` runs without a database and loses it on exit [default: postgres]
+    #[arg(long, env = "AVAILABILITY_STORE", value_enum)]
+    pub store: Option<StoreKind>,
+
+    /// Postgres connection string
+    #[arg(long, env = "DATABASE_URL", hide_env_values = true)]
+    pub database_url: Option<String>,
+
+    /// Most connections the pool opens [default: 5]
+    #[arg(long, env = "DB_MAX_CONNECTIONS")]
+    pub db_max_connections: Option<u32>,
+
+    /// Connections the pool keeps open when idle [default: 0]
+    #[arg(long, env = "DB_MIN_CONNECTIONS")]
+    pub db_min_connections: Option<u32>,
+
+    /// Seconds to wait for a free connection [default: 30]
+    #[arg(long, env = "DB_ACQUIRE_TIMEOUT")]
+    pub db_acquire_timeout: Option<u64>,
+
+    /// Seconds before an unused connection is closed [default: 600]
+    #[arg(long, env = "DB_IDLE_TIMEOUT")]
+    pub db_idle_timeout: Option<u64>,
+
+    /// Don't apply pending migrations at startup
+    #[arg(long, env = "SKIP_MIGRATIONS")]
+    pub skip_migrations: bool,
+
+    /// Directory with the frontend files, nothing is served outside /api without it
+    #[arg(long, env = "STATIC_DIR")]
+    pub static_dir: Option<PathBuf>,
+
+    /// File served for paths not found in the static directory, e.g. the SPA's index.html
+    #[arg(long, env = "STATIC_FALLBACK")]
+    pub static_fallback: Option<PathBuf>,
+
+    /// Log filter, a level or `tracing` directives like `info,sqlx=warn` [default: info]
+    #[arg(long, env = "LOG_LEVEL")]
+    pub log_level: Option<String>,
+
+    /// Log line format [default: text]
+    #[arg(long, env = "LOG_FORMAT", value_enum)]
+    pub log_format: Option<LogFormat>,
+
+    /// Origins allowed to call the API from a browser, comma separated, `*` allows any
+    #[arg(long = "cors-origin", env = "CORS_ORIGINS", value_delimiter = ',')]
+    pub cors_origins: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind: SocketAddr,
+    pub shutdown_timeout_secs: u64,
+    pub store: StoreKind,
+    pub database: DatabaseConfig,
+    #[serde(rename = "static")]
+    pub static_files: StaticConfig,
+    pub log: LogConfig,
+    pub cors: CorsConfig,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: Option<String>,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout_secs: u64,
+    pub idle_timeout_secs: u64,
+    pub skip_migrations: bool,
+}
+
+// Memory keeps everything in the process, for trying the API without a database
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreKind {
+    #[default]
+    Postgres,
+    Memory,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct StaticConfig {
+    pub dir: Option<PathBuf>,
+    pub fallback: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub level: String,
+    pub format: LogFormat,
+}
+
+// JSON puts one object per line with the fields of every open span, for log collectors
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    pub origins: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            bind: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            shutdown_timeout_secs: 30,
+            store: StoreKind::default(),
+            database: DatabaseConfig::default(),
+            static_files: StaticConfig::default(),
+            log: LogConfig::default(),
+            cors: CorsConfig::default(),
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            url: None,
+            max_connections: 5,
+            min_connections: 0,
+            acquire_timeout_secs: 30,
+            idle_timeout_secs: 600,
+            skip_migrations: false,
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: "info".to_string(),
+            format: LogFormat::default(),
+        }
+    }
+}
+
+// The url carries the password, keep it out of logs
+impl fmt::Debug for DatabaseConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DatabaseConfig")
+            .field("url", &self.url.as_ref().map(|_| "<redacted>"))
+            .field("max_connections", &self.max_connections)
+            .field("min_connections", &self.min_connections)
+            .field("acquire_timeout_secs", &self.acquire_timeout_secs)
+            .field("idle_timeout_secs", &self.idle_timeout_secs)
+            .field("skip_migrations", &self.skip_migrations)
+            .finish()
+    }
+}
+
+impl Config {
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_secs)
+    }
+
+    /// Layers the defaults, the config file and then `args`, and checks the result
+    pub fn load(args: &ConfigArgs) -> Result<Config, ConfigError> {
+        let mut config = match &args.config_file {
+            Some(path) => Config::from_file(path)?,
+            None => Config::default(),
+        };
+        config.apply(args);
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config, ConfigError> {
+        let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        toml::from_str(&text).map_err(|source| ConfigError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    fn apply(&mut self, args: &ConfigArgs) {
+        if let Some(bind) = args.bind {
+            self.bind = bind;
+        }
+        if let Some(timeout) = args.shutdown_timeout {
+            self.shutdown_timeout_secs = timeout;
+        }
+        if let Some(store) = args.store {
+            self.store = store;
+        }
+        if let Some(url) = &args.database_url {
+            self.database.url = Some(url.clone());
+        }
+        if let Some(max_connections) = args.db_max_connections {
+            self.database.max_connections = max_connections;
+        }
+        if let Some(min_connections) = args.db_min_connections {
+            self.database.min_connections = min_connections;
+        }
+        if let Some(timeout) = args.db_acquire_timeout {
+            self.database.acquire_timeout_secs = timeout;
+        }
+        if let Some(timeout) = args.db_idle_timeout {
+            self.database.idle_timeout_secs = timeout;
+        }
+        if args.skip_migrations {
+            self.database.skip_migrations = true;
+        }
+        if let Some(dir) = &args.static_dir {
+            self.static_files.dir = Some(dir.clone());
+        }
+        if let Some(fallback) = &args.static_fallback {
+            self.static_files.fallback = Some(fallback.clone());
+        }
+        if let Some(level) = &args.log_level {
+            self.log.level = level.clone();
+        }
+        if let Some(format) = args.log_format {
+            self.log.format = format;
+        }
+        if let Some(origins) = &args.cors_origins {
+            self.cors.origins = origins.clone();
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.database.validate()?;
+        self.static_files.validate()?;
+        self.log.validate()?;
+        self.cors.validate()
+    }
+}
+
+impl DatabaseConfig {
+    pub fn url(&self) -> Result<&str, ConfigError> {
+        self.url.as_deref().ok_or_else(|| {
+            ConfigError::Invalid("database.url is not set, set it or DATABASE_URL".to_string())
+        })
+    }
+
+    pub fn pool_options(&self) -> PgPoolOptions {
+        PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(Duration::from_secs(self.acquire_timeout_secs))
+            .idle_timeout(Duration::from_secs(self.idle_timeout_secs))
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.max_connections == 0 {
+            return Err(ConfigError::Invalid(
+                "database.max_connections must be at least 1".to_string(),
+            ));
+        }
+        if self.min_connections > self.max_connections {
+            return Err(ConfigError::Invalid(format!(
+                "database.min_connections ({}) can't be more than database.max_connections ({})",
+                self.min_connections, self.max_connections
+            )));
+        }
+        if self.acquire_timeout_secs == 0 {
+            return Err(ConfigError::Invalid(
+                "database.acquire_timeout_secs must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl StaticConfig {
+    fn validate(&self) -> Result<(), ConfigError> {
+        if let Some(dir) = &self.dir {
+            if !dir.is_dir() {
+                return Err(ConfigError::Invalid(format!(
+                    "static.dir {} is not a directory",
+                    dir.display()
+                )));
+            }
+        }
+        if let Some(fallback) = &self.fallback {
+            if self.dir.is_none() {
+                return Err(ConfigError::Invalid(
+                    "static.fallback is set without static.dir".to_string(),
+                ));
+            }
+            if !fallback.is_file() {
+                return Err(ConfigError::Invalid(format!(
+                    "static.fallback {} is not a file",
+                    fallback.display()
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl LogConfig {
+    pub fn env_filter(&self) -> Result<EnvFilter, ConfigError> {
+        EnvFilter::try_new(&self.level).map_err(|err| {
+            ConfigError::Invalid(format!("log.level `{}` is invalid: {}", self.level, err))
+        })
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        self.env_filter().map(|_| ())
+    }
+}
+
+impl CorsConfig {
+    fn allows_any(&self) -> bool {
+        self.origins.iter().any(|origin| origin == "*")
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.allows_any() {
+            if self.origins.len() > 1 {
+                return Err(ConfigError::Invalid(
+                    "cors.origins can't list other origins next to `*`".to_string(),
+                ));
+            }
+            return Ok(());
+        }
+        for origin in &self.origins {
+            let scheme = origin.starts_with("http://") || origin.starts_with("https://");
+            if !scheme || origin.ends_with('/') || HeaderValue::from_str(origin).is_err() {
+                return Err(ConfigError::Invalid(format!(
+                    "cors.origins `{}` is not an origin like https://example.com",
+                    origin
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The CORS layer for the configured origins, none when no origins are set.
+    /// Listed origins may send the session cookie, `*` only allows token authentication.
+    pub fn layer(&self) -> Option<CorsLayer> {
+        if self.origins.is_empty() {
+            return None;
+        }
+        let layer = CorsLayer::new()
+            .allow_methods([
+                Method::GET,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ])
+            .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE]);
+        if self.allows_any() {
+            return Some(layer.allow_origin(AllowOrigin::any()));
+        }
+        let origins: Vec<HeaderValue> = self
+            .origins
+            .iter()
+            .filter_map(|origin| HeaderValue::from_str(origin).ok())
+            .collect();
+        Some(layer.allow_origin(AllowOrigin::list(origins)).allow_credentials(true))
+    }
+}
@@ -1,10 +1,19 @@
 use axum::async_trait;
+use tracing::instrument;
 
 use crate::model::*;
 
 #[async_trait]
 #[allow(dead_code)]
 pub trait AvailablityStore {
+    // Health
+    // A cheap round trip, fails when the store can't serve queries
+    async fn ping(&self) -> Result<(), sqlx::error::Error>;
+    // None when the store has no connection pool
+    fn pool_status(&self) -> Option<PoolStatus>;
+    // The newest successfully applied migration, None without a schema
+    async fn applied_migration(&self) -> Result<Option<i64>, sqlx::error::Error>;
+
     // Users
     async fn get_user_by_id(
         &self,
@@ -18,6 +27,31 @@ pub trait AvailablityStore {
     async fn update_user(&self, user: IdentifiableUser) -> Result<IdentifiableUser, sqlx::error::Error>;
     async fn delete_user(&self, user_id: i32) -> Result<(), sqlx::error::Error>;
 
+    // Auth
+    async fn add_user_with_password(
+        &self,
+        user: User,
+        password_hash: String,
+    ) -> Result<IdentifiableUser, sqlx::error::Error>;
+    async fn get_user_credentials_by_name(
+        &self,
+        user_name: String,
+    ) -> Result<Option<UserCredentials>, sqlx::error::Error>;
+    async fn add_session(&self, session: Session) -> Result<Session, sqlx::error::Error>;
+    // Only sessions that haven't expired by `now` resolve to a user
+    async fn get_user_by_session(
+        &self,
+        token_hash: String,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<IdentifiableUser>, sqlx::error::Error>;
+    async fn delete_session(&self, token_hash: String) -> Result<(), sqlx::error::Error>;
+
+    // Admins
+    async fn is_admin(&self, user_id: i32) -> Result<bool, sqlx::error::Error>;
+    // Granting twice or revoking a non-admin changes nothing, a grant to an unknown user is a
+    // foreign key violation
+    async fn set_admin(&self, user_id: i32, admin: bool) -> Result<(), sqlx::error::Error>;
+
     // Teams
     async fn get_team_by_id(
         &self,
@@ -28,12 +62,122 @@ pub trait AvailablityStore {
         team_name: String,
     ) -> Result<Option<IdentifiableTeam>, sqlx::error::Error>;
     async fn add_team(&self, team: Team) -> Result<IdentifiableTeam, sqlx::error::Error>;
+    // Creates the team and its owner membership together
+    async fn add_team_with_owner(
+        &self,
+        team: Team,
+        player_id: i32,
+    ) -> Result<IdentifiableTeam, sqlx::error::Error>;
     async fn update_team(
         &self,
         team: IdentifiableTeam,
     ) -> Result<IdentifiableTeam, sqlx::error::Error>;
     async fn delete_team(&self, team_id: i32) -> Result<(), sqlx::error::Error>;
 
+    // Invites
+    async fn get_team_invite(
+        &self,
+        team_id: i32,
+    ) -> Result<Option<TeamInvite>, sqlx::error::Error>;
+    async fn get_team_invite_by_code(
+        &self,
+        code: String,
+    ) -> Result<Option<TeamInvite>, sqlx::error::Error>;
+    // Replaces the team's code, RowNotFound when the team doesn't exist
+    async fn set_team_invite(&self, invite: TeamInvite) -> Result<TeamInvite, sqlx::error::Error>;
+    async fn delete_team_invite(&self, team_id: i32) -> Result<(), sqlx::error::Error>;
+    // Counts a use and adds the player as a member in one go, None when the code is
+    // unknown, expired or used up by the time it is redeemed
+    async fn redeem_team_invite(
+        &self,
+        code: String,
+        player_id: i32,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<PlayerToTeam>, sqlx::error::Error>;
+
+    // Feeds
+    async fn get_team_feed(&self, team_id: i32) -> Result<Option<TeamFeed>, sqlx::error::Error>;
+    async fn get_team_feed_by_token(
+        &self,
+        token: String,
+    ) -> Result<Option<TeamFeed>, sqlx::error::Error>;
+    // Replaces the team's token, RowNotFound when the team doesn't exist
+    async fn set_team_feed(&self, feed: TeamFeed) -> Result<TeamFeed, sqlx::error::Error>;
+    async fn delete_team_feed(&self, team_id: i32) -> Result<(), sqlx::error::Error>;
+    async fn get_player_feed(
+        &self,
+        player_id: i32,
+    ) -> Result<Option<PlayerFeed>, sqlx::error::Error>;
+    async fn get_player_feed_by_token(
+        &self,
+        token: String,
+    ) -> Result<Option<PlayerFeed>, sqlx::error::Error>;
+    // Replaces the player's token, RowNotFound when the player doesn't exist
+    async fn set_player_feed(&self, feed: PlayerFeed) -> Result<PlayerFeed, sqlx::error::Error>;
+    async fn delete_player_feed(&self, player_id: i32) -> Result<(), sqlx::error::Error>;
+
+    // Events
+    async fn add_team_event(
+        &self,
+        team_id: i32,
+        event: TeamEvent,
+        created_by: i32,
+    ) -> Result<IdentifiableTeamEvent, sqlx::error::Error>;
+    async fn get_team_event_by_id(
+        &self,
+        event_id: i32,
+    ) -> Result<Option<IdentifiableTeamEvent>, sqlx::error::Error>;
+    // Ordered by start
+    async fn get_team_events_by_team_id(
+        &self,
+        team_id: i32,
+    ) -> Result<Vec<IdentifiableTeamEvent>, sqlx::error::Error>;
+    // Replaces the event's details, RowNotFound when it doesn't exist
+    async fn update_team_event(
+        &self,
+        event: IdentifiableTeamEvent,
+    ) -> Result<IdentifiableTeamEvent, sqlx::error::Error>;
+    async fn delete_team_event(&self, event_id: i32) -> Result<(), sqlx::error::Error>;
+
+    // RSVPs
+    // Replaces the player's earlier answer
+    async fn set_rsvp(
+        &self,
+        event_id: i32,
+        player_id: i32,
+        response: RsvpResponse,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Rsvp, sqlx::error::Error>;
+    async fn get_rsvps_by_event_id(&self, event_id: i32) -> Result<Vec<Rsvp>, sqlx::error::Error>;
+    async fn delete_rsvp(&self, event_id: i32, player_id: i32) -> Result<(), sqlx::error::Error>;
+
+    // Join requests
+    async fn add_join_request(
+        &self,
+        team_id: i32,
+        player_id: i32,
+        message: Option<String>,
+    ) -> Result<JoinRequest, sqlx::error::Error>;
+    async fn get_join_request_by_id(
+        &self,
+        request_id: i32,
+    ) -> Result<Option<JoinRequest>, sqlx::error::Error>;
+    // Oldest first, all of them when no status is given
+    async fn get_join_requests_by_team_id(
+        &self,
+        team_id: i32,
+        status: Option<JoinRequestStatus>,
+    ) -> Result<Vec<JoinRequest>, sqlx::error::Error>;
+    // Approving also adds the player to the team as a member, None when the request is no
+    // longer pending
+    async fn resolve_join_request(
+        &self,
+        request_id: i32,
+        status: JoinRequestStatus,
+        resolved_by: i32,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<JoinRequest>, sqlx::error::Error>;
+
     // Players
     async fn get_player_by_id(
         &self,
@@ -50,6 +194,37 @@ pub trait AvailablityStore {
     ) -> Result<IdentifiablePlayer, sqlx::error::Error>;
     async fn delete_player(&self, player_id: i32) -> Result<(), sqlx::error::Error>;
 
+    // Rosters
+    async fn add_player_to_team(
+        &self,
+        membership: PlayerToTeam,
+    ) -> Result<PlayerToTeam, sqlx::error::Error>;
+    async fn remove_player_from_team(
+        &self,
+        membership: PlayerToTeam,
+    ) -> Result<(), sqlx::error::Error>;
+    async fn update_team_role(
+        &self,
+        membership: PlayerToTeam,
+    ) -> Result<PlayerToTeam, sqlx::error::Error>;
+    async fn get_team_membership(
+        &self,
+        team_id: i32,
+        player_id: i32,
+    ) -> Result<Option<PlayerToTeam>, sqlx::error::Error>;
+    async fn get_memberships_by_player_id(
+        &self,
+        player_id: i32,
+    ) -> Result<Vec<PlayerToTeam>, sqlx::error::Error>;
+    async fn get_team_roster(
+        &self,
+        team_id: i32,
+    ) -> Result<Vec<RosterMember>, sqlx::error::Error>;
+    async fn get_teams_by_player_id(
+        &self,
+        player_id: i32,
+    ) -> Result<Vec<IdentifiableTeam>, sqlx::error::Error>;
+
     // Avail Blocks
     async fn get_available_block_by_id(
         &self,
@@ -59,15 +234,51 @@ pub trait AvailablityStore {
         &self,
         player_id: i32,
     ) -> Result<Vec<IdentifiableAvailableBlock>, sqlx::error::Error>;
+    async fn get_raw_available_blocks(&self) -> Result<Vec<RawAvailableBlock>, sqlx::error::Error>;
+    async fn get_raw_available_blocks_by_player_id(
+        &self,
+        player_id: i32,
+    ) -> Result<Vec<RawAvailableBlock>, sqlx::error::Error>;
+    async fn get_raw_available_blocks_by_team_id(
+        &self,
+        team_id: i32,
+    ) -> Result<Vec<RawAvailableBlock>, sqlx::error::Error>;
     async fn add_available_block(
         &self,
         block: AvailableBlock,
     ) -> Result<IdentifiableAvailableBlock, sqlx::error::Error>;
+    // All or none of them are added
+    async fn add_available_blocks(
+        &self,
+        blocks: Vec<AvailableBlock>,
+    ) -> Result<Vec<IdentifiableAvailableBlock>, sqlx::error::Error>;
     async fn update_available_block(
         &self,
         block: IdentifiableAvailableBlock,
     ) -> Result<IdentifiableAvailableBlock, sqlx::error::Error>;
     async fn delete_available_block(&self, block_id: i32) -> Result<(), sqlx::error::Error>;
+    // Just the player_id, works even when the stored rule doesn't parse
+    async fn get_available_block_player_id(
+        &self,
+        block_id: i32,
+    ) -> Result<Option<i32>, sqlx::error::Error>;
+}
+
+// Parses the stored rule and timezone, a malformed value becomes a decode error instead of a panic
+fn decode_block(raw: RawAvailableBlock) -> Result<IdentifiableAvailableBlock, sqlx::error::Error> {
+    let block_id = raw.id;
+    IdentifiableAvailableBlock::try_from(raw).map_err(|err| sqlx::error::Error::ColumnDecode {
+        index: format!("available block {}", block_id),
+        source: Box::new(err),
+    })
+}
+
+fn decode_event(raw: RawTeamEvent) -> Result<IdentifiableTeamEvent, sqlx::error::Error> {
+    let event_id = raw.id;
+    IdentifiableTeamEvent::try_from(raw).map_err(|err| sqlx::error::Error::ColumnDecode {
+        index: format!("team event {}", event_id),
+        source: Box::new(err),
+    })
 }
 
 //TODO: Change updates to have thier own data type
@@ -81,9 +292,37 @@ impl PostgresAvailablityStore {
     }
 }
 
+// Every method runs in a debug span with the ids it was called with, closing the span logs
+// how long the queries took and errors are recorded on it
 #[async_trait]
 impl AvailablityStore for PostgresAvailablityStore {
+    //Health
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn ping(&self) -> Result<(), sqlx::error::Error> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    fn pool_status(&self) -> Option<PoolStatus> {
+        Some(PoolStatus {
+            size: self.pool.size(),
+            idle: self.pool.num_idle() as u32,
+            max: self.pool.options().get_max_connections(),
+        })
+    }
+
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn applied_migration(&self) -> Result<Option<i64>, sqlx::error::Error> {
+        // Not a checked query, the migrations table isn't part of the schema
+        sqlx::query_scalar(
+            "SELECT max(version) FROM _sqlx_migrations WHERE success",
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
     //Users
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
     async fn get_user_by_id(
         &self,
         user_id: i32,
@@ -91,13 +330,14 @@ impl AvailablityStore for PostgresAvailablityStore {
         sqlx::query_as!(
             IdentifiableUser,
             //Id's are unique should only return one user
-            "SELECT * FROM users WHERE id=$1",
+            "SELECT id, name FROM users WHERE id=$1",
             user_id,
         )
         .fetch_optional(&self.pool)
         .await
     }
 
+    #[instrument(level = "debug", skip(self, user_name), err(level = "debug"))]
     async fn get_user_by_name(
         &self,
         user_name: String,
@@ -105,13 +345,14 @@ impl AvailablityStore for PostgresAvailablityStore {
         sqlx::query_as!(
             IdentifiableUser,
             //Id's are unique should only return one user
-            "SELECT * FROM users WHERE name=$1",
+            "SELECT id, name FROM users WHERE name=$1",
             user_name,
         )
         .fetch_optional(&self.pool)
         .await
     }
 
+    #[instrument(level = "debug", skip(self, user), err(level = "debug"))]
     async fn add_user(&self, user: User) -> Result<IdentifiableUser, sqlx::error::Error> {
         sqlx::query_as!(
             IdentifiableUser,
@@ -122,6 +363,7 @@ impl AvailablityStore for PostgresAvailablityStore {
         .await
     }
 
+    #[instrument(level = "debug", skip(self, user), err(level = "debug"))]
     async fn update_user(&self, user: IdentifiableUser) -> Result<IdentifiableUser, sqlx::error::Error> {
         sqlx::query_as!(
             IdentifiableUser,
@@ -133,6 +375,7 @@ impl AvailablityStore for PostgresAvailablityStore {
         .await
     }
 
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
     async fn delete_user(&self, user_id: i32) -> Result<(), sqlx::error::Error> {
         sqlx::query!("DELETE FROM users WHERE id=$1", user_id)
             .execute(&self.pool)
@@ -140,7 +383,108 @@ impl AvailablityStore for PostgresAvailablityStore {
         Ok(())
     }
 
+    //Auth
+    #[instrument(level = "debug", skip(self, user, password_hash), err(level = "debug"))]
+    async fn add_user_with_password(
+        &self,
+        user: User,
+        password_hash: String,
+    ) -> Result<IdentifiableUser, sqlx::error::Error> {
+        sqlx::query_as!(
+            IdentifiableUser,
+            "INSERT INTO users(name, password_hash) VALUES ($1, $2) RETURNING id, name",
+            user.name,
+            password_hash
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self, user_name), err(level = "debug"))]
+    async fn get_user_credentials_by_name(
+        &self,
+        user_name: String,
+    ) -> Result<Option<UserCredentials>, sqlx::error::Error> {
+        sqlx::query_as!(
+            UserCredentials,
+            "SELECT id, name, password_hash FROM users WHERE name=$1",
+            user_name,
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self, session), err(level = "debug"))]
+    async fn add_session(&self, session: Session) -> Result<Session, sqlx::error::Error> {
+        sqlx::query_as!(
+            Session,
+            "INSERT INTO sessions(token_hash, user_id, created_at, expires_at) VALUES ($1, $2, $3, $4)
+            RETURNING token_hash, user_id, created_at, expires_at",
+            session.token_hash,
+            session.user_id,
+            session.created_at,
+            session.expires_at
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self, token_hash, now), err(level = "debug"))]
+    async fn get_user_by_session(
+        &self,
+        token_hash: String,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<IdentifiableUser>, sqlx::error::Error> {
+        sqlx::query_as!(
+            IdentifiableUser,
+            "SELECT users.id, users.name FROM sessions
+            JOIN users ON users.id = sessions.user_id
+            WHERE sessions.token_hash=$1 AND sessions.expires_at > $2",
+            token_hash,
+            now
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self, token_hash), err(level = "debug"))]
+    async fn delete_session(&self, token_hash: String) -> Result<(), sqlx::error::Error> {
+        sqlx::query!("DELETE FROM sessions WHERE token_hash=$1", token_hash)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    //Admins
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn is_admin(&self, user_id: i32) -> Result<bool, sqlx::error::Error> {
+        sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM admins WHERE user_id=$1) AS "admin!""#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn set_admin(&self, user_id: i32, admin: bool) -> Result<(), sqlx::error::Error> {
+        if admin {
+            sqlx::query!(
+                "INSERT INTO admins(user_id) VALUES ($1) ON CONFLICT (user_id) DO NOTHING",
+                user_id
+            )
+            .execute(&self.pool)
+            .await?;
+        } else {
+            sqlx::query!("DELETE FROM admins WHERE user_id=$1", user_id)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
     //Teams
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
     async fn get_team_by_id(
         &self,
         team_id: i32,
@@ -148,13 +492,14 @@ impl AvailablityStore for PostgresAvailablityStore {
         sqlx::query_as!(
             IdentifiableTeam,
             //Id's are unique should only return one user
-            "SELECT * FROM teams WHERE id=$1",
+            "SELECT id, name FROM teams WHERE id=$1",
             team_id,
         )
         .fetch_optional(&self.pool)
         .await
     }
 
+    #[instrument(level = "debug", skip(self, team_name), err(level = "debug"))]
     async fn get_team_by_name(
         &self,
         team_name: String,
@@ -162,13 +507,14 @@ impl AvailablityStore for PostgresAvailablityStore {
         sqlx::query_as!(
             IdentifiableTeam,
             //Id's are unique should only return one user
-            "SELECT * FROM teams WHERE name=$1",
+            "SELECT id, name FROM teams WHERE name=$1",
             team_name,
         )
         .fetch_optional(&self.pool)
         .await
     }
 
+    #[instrument(level = "debug", skip(self, team), err(level = "debug"))]
     async fn add_team(&self, team: Team) -> Result<IdentifiableTeam, sqlx::error::Error> {
         sqlx::query_as!(
             IdentifiableTeam,
@@ -179,13 +525,40 @@ impl AvailablityStore for PostgresAvailablityStore {
         .await
     }
 
+    #[instrument(level = "debug", skip(self, team), err(level = "debug"))]
+    async fn add_team_with_owner(
+        &self,
+        team: Team,
+        player_id: i32,
+    ) -> Result<IdentifiableTeam, sqlx::error::Error> {
+        let mut tx = self.pool.begin().await?;
+        let team = sqlx::query_as!(
+            IdentifiableTeam,
+            "INSERT INTO teams(name) VALUES ($1) RETURNING id, name",
+            team.name
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "INSERT INTO players_to_teams(player_id, team_id, role) VALUES ($1, $2, $3)",
+            player_id,
+            team.id,
+            TeamRole::Owner as TeamRole
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(team)
+    }
+
+    #[instrument(level = "debug", skip(self, team), err(level = "debug"))]
     async fn update_team(
         &self,
         team: IdentifiableTeam,
     ) -> Result<IdentifiableTeam, sqlx::error::Error> {
         sqlx::query_as!(
             IdentifiableTeam,
-            "UPDATE users SET name=$1 WHERE id=$2 RETURNING id, name",
+            "UPDATE teams SET name=$1 WHERE id=$2 RETURNING id, name",
             team.name,
             team.id
         )
@@ -193,6 +566,7 @@ impl AvailablityStore for PostgresAvailablityStore {
         .await
     }
 
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
     async fn delete_team(&self, team_id: i32) -> Result<(), sqlx::error::Error> {
         sqlx::query!("DELETE FROM teams WHERE id=$1", team_id)
             .execute(&self.pool)
@@ -200,7 +574,442 @@ impl AvailablityStore for PostgresAvailablityStore {
         Ok(())
     }
 
+    //Invites
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn get_team_invite(
+        &self,
+        team_id: i32,
+    ) -> Result<Option<TeamInvite>, sqlx::error::Error> {
+        sqlx::query_as!(
+            TeamInvite,
+            r#"SELECT id AS team_id, invite_code AS "code!", invite_expires_at AS expires_at,
+                invite_max_uses AS max_uses, invite_uses AS uses
+            FROM teams WHERE id=$1 AND invite_code IS NOT NULL"#,
+            team_id,
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self, code), err(level = "debug"))]
+    async fn get_team_invite_by_code(
+        &self,
+        code: String,
+    ) -> Result<Option<TeamInvite>, sqlx::error::Error> {
+        sqlx::query_as!(
+            TeamInvite,
+            r#"SELECT id AS team_id, invite_code AS "code!", invite_expires_at AS expires_at,
+                invite_max_uses AS max_uses, invite_uses AS uses
+            FROM teams WHERE invite_code=$1"#,
+            code,
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self, invite), err(level = "debug"))]
+    async fn set_team_invite(&self, invite: TeamInvite) -> Result<TeamInvite, sqlx::error::Error> {
+        sqlx::query_as!(
+            TeamInvite,
+            r#"UPDATE teams SET invite_code=$1, invite_expires_at=$2, invite_max_uses=$3, invite_uses=$4
+            WHERE id=$5
+            RETURNING id AS team_id, invite_code AS "code!", invite_expires_at AS expires_at,
+                invite_max_uses AS max_uses, invite_uses AS uses"#,
+            invite.code,
+            invite.expires_at,
+            invite.max_uses,
+            invite.uses,
+            invite.team_id
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn delete_team_invite(&self, team_id: i32) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "UPDATE teams SET invite_code=NULL, invite_expires_at=NULL, invite_max_uses=NULL, invite_uses=0
+            WHERE id=$1",
+            team_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip(self, code, now), err(level = "debug"))]
+    async fn redeem_team_invite(
+        &self,
+        code: String,
+        player_id: i32,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<PlayerToTeam>, sqlx::error::Error> {
+        let mut tx = self.pool.begin().await?;
+        // The row lock taken by the update keeps concurrent redemptions under max_uses
+        let team_id = sqlx::query_scalar!(
+            "UPDATE teams SET invite_uses = invite_uses + 1
+            WHERE invite_code=$1
+                AND (invite_expires_at IS NULL OR invite_expires_at > $2)
+                AND (invite_max_uses IS NULL OR invite_uses < invite_max_uses)
+            RETURNING id",
+            code,
+            now
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(team_id) = team_id else {
+            return Ok(None);
+        };
+        let membership = sqlx::query_as!(
+            PlayerToTeam,
+            r#"INSERT INTO players_to_teams(player_id, team_id, role) VALUES ($1, $2, $3)
+            RETURNING player_id, team_id, role AS "role: TeamRole""#,
+            player_id,
+            team_id,
+            TeamRole::Member as TeamRole
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(Some(membership))
+    }
+
+    //Feeds
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn get_team_feed(&self, team_id: i32) -> Result<Option<TeamFeed>, sqlx::error::Error> {
+        sqlx::query_as!(
+            TeamFeed,
+            r#"SELECT id AS team_id, feed_token AS "token!" FROM teams
+            WHERE id=$1 AND feed_token IS NOT NULL"#,
+            team_id,
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self, token), err(level = "debug"))]
+    async fn get_team_feed_by_token(
+        &self,
+        token: String,
+    ) -> Result<Option<TeamFeed>, sqlx::error::Error> {
+        sqlx::query_as!(
+            TeamFeed,
+            r#"SELECT id AS team_id, feed_token AS "token!" FROM teams WHERE feed_token=$1"#,
+            token,
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self, feed), err(level = "debug"))]
+    async fn set_team_feed(&self, feed: TeamFeed) -> Result<TeamFeed, sqlx::error::Error> {
+        sqlx::query_as!(
+            TeamFeed,
+            r#"UPDATE teams SET feed_token=$1 WHERE id=$2
+            RETURNING id AS team_id, feed_token AS "token!""#,
+            feed.token,
+            feed.team_id
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn delete_team_feed(&self, team_id: i32) -> Result<(), sqlx::error::Error> {
+        sqlx::query!("UPDATE teams SET feed_token=NULL WHERE id=$1", team_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn get_player_feed(
+        &self,
+        player_id: i32,
+    ) -> Result<Option<PlayerFeed>, sqlx::error::Error> {
+        sqlx::query_as!(
+            PlayerFeed,
+            r#"SELECT id AS player_id, feed_token AS "token!" FROM players
+            WHERE id=$1 AND feed_token IS NOT NULL"#,
+            player_id,
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self, token), err(level = "debug"))]
+    async fn get_player_feed_by_token(
+        &self,
+        token: String,
+    ) -> Result<Option<PlayerFeed>, sqlx::error::Error> {
+        sqlx::query_as!(
+            PlayerFeed,
+            r#"SELECT id AS player_id, feed_token AS "token!" FROM players WHERE feed_token=$1"#,
+            token,
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self, feed), err(level = "debug"))]
+    async fn set_player_feed(&self, feed: PlayerFeed) -> Result<PlayerFeed, sqlx::error::Error> {
+        sqlx::query_as!(
+            PlayerFeed,
+            r#"UPDATE players SET feed_token=$1 WHERE id=$2
+            RETURNING id AS player_id, feed_token AS "token!""#,
+            feed.token,
+            feed.player_id
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn delete_player_feed(&self, player_id: i32) -> Result<(), sqlx::error::Error> {
+        sqlx::query!("UPDATE players SET feed_token=NULL WHERE id=$1", player_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    //Events
+    #[instrument(level = "debug", skip(self, event), err(level = "debug"))]
+    async fn add_team_event(
+        &self,
+        team_id: i32,
+        event: TeamEvent,
+        created_by: i32,
+    ) -> Result<IdentifiableTeamEvent, sqlx::error::Error> {
+        sqlx::query_as!(
+            RawTeamEvent,
+            "INSERT INTO team_events(team_id, title, starts_at, ends_at, repeats, location, notes, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id, team_id, title, starts_at, ends_at, repeats, location, notes, created_by, created_at",
+            team_id,
+            event.title,
+            event.start,
+            event.end,
+            event.repeats.map(|repeats| repeats.to_string()),
+            event.location,
+            event.notes,
+            created_by
+        )
+        .fetch_one(&self.pool)
+        .await
+        .and_then(decode_event)
+    }
+
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn get_team_event_by_id(
+        &self,
+        event_id: i32,
+    ) -> Result<Option<IdentifiableTeamEvent>, sqlx::error::Error> {
+        sqlx::query_as!(
+            RawTeamEvent,
+            "SELECT id, team_id, title, starts_at, ends_at, repeats, location, notes, created_by, created_at
+            FROM team_events WHERE id=$1",
+            event_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .map(decode_event)
+        .transpose()
+    }
+
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn get_team_events_by_team_id(
+        &self,
+        team_id: i32,
+    ) -> Result<Vec<IdentifiableTeamEvent>, sqlx::error::Error> {
+        sqlx::query_as!(
+            RawTeamEvent,
+            "SELECT id, team_id, title, starts_at, ends_at, repeats, location, notes, created_by, created_at
+            FROM team_events WHERE team_id=$1 ORDER BY starts_at, id",
+            team_id
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(decode_event)
+        .collect()
+    }
+
+    #[instrument(level = "debug", skip(self, event), err(level = "debug"))]
+    async fn update_team_event(
+        &self,
+        event: IdentifiableTeamEvent,
+    ) -> Result<IdentifiableTeamEvent, sqlx::error::Error> {
+        let inner = event.inner_event;
+        sqlx::query_as!(
+            RawTeamEvent,
+            "UPDATE team_events SET title=$1, starts_at=$2, ends_at=$3, repeats=$4, location=$5, notes=$6
+            WHERE id=$7
+            RETURNING id, team_id, title, starts_at, ends_at, repeats, location, notes, created_by, created_at",
+            inner.title,
+            inner.start,
+            inner.end,
+            inner.repeats.map(|repeats| repeats.to_string()),
+            inner.location,
+            inner.notes,
+            event.id
+        )
+        .fetch_one(&self.pool)
+        .await
+        .and_then(decode_event)
+    }
+
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn delete_team_event(&self, event_id: i32) -> Result<(), sqlx::error::Error> {
+        sqlx::query!("DELETE FROM team_events WHERE id=$1", event_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    //RSVPs
+    #[instrument(level = "debug", skip(self, response, now), err(level = "debug"))]
+    async fn set_rsvp(
+        &self,
+        event_id: i32,
+        player_id: i32,
+        response: RsvpResponse,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Rsvp, sqlx::error::Error> {
+        sqlx::query_as!(
+            Rsvp,
+            r#"INSERT INTO event_rsvps(event_id, player_id, response, updated_at) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (event_id, player_id) DO UPDATE SET response=$3, updated_at=$4
+            RETURNING event_id, player_id, response AS "response: RsvpResponse", updated_at"#,
+            event_id,
+            player_id,
+            response as RsvpResponse,
+            now
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn get_rsvps_by_event_id(&self, event_id: i32) -> Result<Vec<Rsvp>, sqlx::error::Error> {
+        sqlx::query_as!(
+            Rsvp,
+            r#"SELECT event_id, player_id, response AS "response: RsvpResponse", updated_at
+            FROM event_rsvps WHERE event_id=$1 ORDER BY player_id"#,
+            event_id
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn delete_rsvp(&self, event_id: i32, player_id: i32) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "DELETE FROM event_rsvps WHERE event_id=$1 AND player_id=$2",
+            event_id,
+            player_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    //Join requests
+    #[instrument(level = "debug", skip(self, message), err(level = "debug"))]
+    async fn add_join_request(
+        &self,
+        team_id: i32,
+        player_id: i32,
+        message: Option<String>,
+    ) -> Result<JoinRequest, sqlx::error::Error> {
+        sqlx::query_as!(
+            JoinRequest,
+            r#"INSERT INTO join_requests(team_id, player_id, message) VALUES ($1, $2, $3)
+            RETURNING id, team_id, player_id, status AS "status: JoinRequestStatus", message,
+                created_at, resolved_at, resolved_by"#,
+            team_id,
+            player_id,
+            message
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn get_join_request_by_id(
+        &self,
+        request_id: i32,
+    ) -> Result<Option<JoinRequest>, sqlx::error::Error> {
+        sqlx::query_as!(
+            JoinRequest,
+            r#"SELECT id, team_id, player_id, status AS "status: JoinRequestStatus", message,
+                created_at, resolved_at, resolved_by
+            FROM join_requests WHERE id=$1"#,
+            request_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self, status), err(level = "debug"))]
+    async fn get_join_requests_by_team_id(
+        &self,
+        team_id: i32,
+        status: Option<JoinRequestStatus>,
+    ) -> Result<Vec<JoinRequest>, sqlx::error::Error> {
+        sqlx::query_as!(
+            JoinRequest,
+            r#"SELECT id, team_id, player_id, status AS "status: JoinRequestStatus", message,
+                created_at, resolved_at, resolved_by
+            FROM join_requests WHERE team_id=$1 AND ($2::text IS NULL OR status=$2)
+            ORDER BY created_at, id"#,
+            team_id,
+            status as Option<JoinRequestStatus>
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self, status, now), err(level = "debug"))]
+    async fn resolve_join_request(
+        &self,
+        request_id: i32,
+        status: JoinRequestStatus,
+        resolved_by: i32,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<JoinRequest>, sqlx::error::Error> {
+        let mut tx = self.pool.begin().await?;
+        let request = sqlx::query_as!(
+            JoinRequest,
+            r#"UPDATE join_requests SET status=$1, resolved_at=$2, resolved_by=$3
+            WHERE id=$4 AND status='pending'
+            RETURNING id, team_id, player_id, status AS "status: JoinRequestStatus", message,
+                created_at, resolved_at, resolved_by"#,
+            status as JoinRequestStatus,
+            now,
+            resolved_by,
+            request_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(request) = request else {
+            return Ok(None);
+        };
+        if request.status == JoinRequestStatus::Approved {
+            sqlx::query!(
+                "INSERT INTO players_to_teams(player_id, team_id, role) VALUES ($1, $2, $3)",
+                request.player_id,
+                request.team_id,
+                TeamRole::Member as TeamRole
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(Some(request))
+    }
+
     //Players
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
     async fn get_player_by_id(
         &self,
         player_id: i32,
@@ -208,13 +1017,14 @@ impl AvailablityStore for PostgresAvailablityStore {
         sqlx::query_as!(
             IdentifiablePlayer,
             //Id's are unique should only return one user
-            "SELECT * FROM players WHERE id=$1",
+            "SELECT id, user_id FROM players WHERE id=$1",
             player_id,
         )
         .fetch_optional(&self.pool)
         .await
     }
 
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
     async fn get_player_by_user_id(
         &self,
         user_id: i32,
@@ -222,23 +1032,25 @@ impl AvailablityStore for PostgresAvailablityStore {
         sqlx::query_as!(
             IdentifiablePlayer,
             //Id's are unique should only return one user
-            "SELECT * FROM players WHERE user_id=$1",
+            "SELECT id, user_id FROM players WHERE user_id=$1",
             user_id,
         )
         .fetch_optional(&self.pool)
         .await
     }
 
+    #[instrument(level = "debug", skip(self, player), err(level = "debug"))]
     async fn add_player(&self, player: Player) -> Result<IdentifiablePlayer, sqlx::error::Error> {
         sqlx::query_as!(
             IdentifiablePlayer,
-            "INSERT INTO players VALUES ($1) RETURNING id, user_id",
+            "INSERT INTO players(user_id) VALUES ($1) RETURNING id, user_id",
             player.user_id
         )
         .fetch_one(&self.pool)
         .await
     }
 
+    #[instrument(level = "debug", skip(self, player), err(level = "debug"))]
     async fn update_player(
         &self,
         player: IdentifiablePlayer,
@@ -253,81 +1065,304 @@ impl AvailablityStore for PostgresAvailablityStore {
         .await
     }
 
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
     async fn delete_player(&self, player_id: i32) -> Result<(), sqlx::error::Error> {
-        sqlx::query!("DELETE FROM users WHERE id=$1", player_id)
+        sqlx::query!("DELETE FROM players WHERE id=$1", player_id)
             .execute(&self.pool)
             .await?;
         Ok(())
     }
 
+    //Rosters
+    #[instrument(level = "debug", skip(self, membership), err(level = "debug"))]
+    async fn add_player_to_team(
+        &self,
+        membership: PlayerToTeam,
+    ) -> Result<PlayerToTeam, sqlx::error::Error> {
+        sqlx::query_as!(
+            PlayerToTeam,
+            r#"INSERT INTO players_to_teams(player_id, team_id, role) VALUES ($1, $2, $3)
+            RETURNING player_id, team_id, role AS "role: TeamRole""#,
+            membership.player_id,
+            membership.team_id,
+            membership.role as TeamRole
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self, membership), err(level = "debug"))]
+    async fn remove_player_from_team(
+        &self,
+        membership: PlayerToTeam,
+    ) -> Result<(), sqlx::error::Error> {
+        let result = sqlx::query!(
+            "DELETE FROM players_to_teams WHERE player_id=$1 AND team_id=$2",
+            membership.player_id,
+            membership.team_id
+        )
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::error::Error::RowNotFound);
+        }
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip(self, membership), err(level = "debug"))]
+    async fn update_team_role(
+        &self,
+        membership: PlayerToTeam,
+    ) -> Result<PlayerToTeam, sqlx::error::Error> {
+        sqlx::query_as!(
+            PlayerToTeam,
+            r#"UPDATE players_to_teams SET role=$1 WHERE player_id=$2 AND team_id=$3
+            RETURNING player_id, team_id, role AS "role: TeamRole""#,
+            membership.role as TeamRole,
+            membership.player_id,
+            membership.team_id
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn get_team_membership(
+        &self,
+        team_id: i32,
+        player_id: i32,
+    ) -> Result<Option<PlayerToTeam>, sqlx::error::Error> {
+        sqlx::query_as!(
+            PlayerToTeam,
+            r#"SELECT player_id, team_id, role AS "role: TeamRole" FROM players_to_teams
+            WHERE team_id=$1 AND player_id=$2"#,
+            team_id,
+            player_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn get_memberships_by_player_id(
+        &self,
+        player_id: i32,
+    ) -> Result<Vec<PlayerToTeam>, sqlx::error::Error> {
+        sqlx::query_as!(
+            PlayerToTeam,
+            r#"SELECT player_id, team_id, role AS "role: TeamRole" FROM players_to_teams
+            WHERE player_id=$1"#,
+            player_id
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn get_team_roster(
+        &self,
+        team_id: i32,
+    ) -> Result<Vec<RosterMember>, sqlx::error::Error> {
+        sqlx::query_as!(
+            RosterMember,
+            r#"SELECT players.id AS player_id, users.id AS user_id, users.name,
+                players_to_teams.role AS "role: TeamRole"
+            FROM players_to_teams
+            JOIN players ON players.id = players_to_teams.player_id
+            JOIN users ON users.id = players.user_id
+            WHERE players_to_teams.team_id=$1
+            ORDER BY users.name"#,
+            team_id,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn get_teams_by_player_id(
+        &self,
+        player_id: i32,
+    ) -> Result<Vec<IdentifiableTeam>, sqlx::error::Error> {
+        sqlx::query_as!(
+            IdentifiableTeam,
+            "SELECT teams.id, teams.name
+            FROM players_to_teams
+            JOIN teams ON teams.id = players_to_teams.team_id
+            WHERE players_to_teams.player_id=$1
+            ORDER BY teams.name",
+            player_id,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
     // Blocks
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
     async fn get_available_block_by_id(
         &self,
         block_id: i32,
     ) -> Result<Option<IdentifiableAvailableBlock>, sqlx::error::Error> {
-        sqlx::query_as::<_, IdentifiableAvailableBlock>(
+        sqlx::query_as!(
+            RawAvailableBlock,
             //Id's are unique should only return one block
-            "SELECT * FROM available_blocks WHERE id=$1",
+            "SELECT id, start_time, end_time, need_warning, repeats, player_id, timezone
+            FROM available_blocks WHERE id=$1",
+            block_id,
         )
-        .bind(block_id)
         .fetch_optional(&self.pool)
-        .await
+        .await?
+        .map(decode_block)
+        .transpose()
     }
 
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
     async fn get_available_blocks_by_player_id(
         &self,
         player_id: i32,
     ) -> Result<Vec<IdentifiableAvailableBlock>, sqlx::error::Error> {
-        sqlx::query_as::<_, IdentifiableAvailableBlock>(
-            //Id's are unique should only return one player's blocks
-            "SELECT * FROM available_blocks WHERE player_id=$1",
+        self.get_raw_available_blocks_by_player_id(player_id)
+            .await?
+            .into_iter()
+            .map(decode_block)
+            .collect()
+    }
+
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn get_raw_available_blocks(&self) -> Result<Vec<RawAvailableBlock>, sqlx::error::Error> {
+        sqlx::query_as!(
+            RawAvailableBlock,
+            "SELECT id, start_time, end_time, need_warning, repeats, player_id, timezone
+            FROM available_blocks ORDER BY id",
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn get_raw_available_blocks_by_player_id(
+        &self,
+        player_id: i32,
+    ) -> Result<Vec<RawAvailableBlock>, sqlx::error::Error> {
+        sqlx::query_as!(
+            RawAvailableBlock,
+            "SELECT id, start_time, end_time, need_warning, repeats, player_id, timezone
+            FROM available_blocks WHERE player_id=$1 ORDER BY id",
+            player_id,
         )
-        .bind(player_id)
         .fetch_all(&self.pool)
         .await
     }
 
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn get_raw_available_blocks_by_team_id(
+        &self,
+        team_id: i32,
+    ) -> Result<Vec<RawAvailableBlock>, sqlx::error::Error> {
+        sqlx::query_as!(
+            RawAvailableBlock,
+            "SELECT blocks.id, blocks.start_time, blocks.end_time, blocks.need_warning,
+                blocks.repeats, blocks.player_id, blocks.timezone
+            FROM available_blocks blocks
+            JOIN players_to_teams ON players_to_teams.player_id = blocks.player_id
+            WHERE players_to_teams.team_id=$1
+            ORDER BY blocks.id",
+            team_id,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[instrument(level = "debug", skip(self, block), err(level = "debug"))]
     async fn add_available_block(
         &self,
         block: AvailableBlock,
     ) -> Result<IdentifiableAvailableBlock, sqlx::error::Error> {
-        sqlx::query_as::<_,IdentifiableAvailableBlock>(
-            "INSERT INTO available_blocks (start_time, end_time, needs_waring, repeats, player_id) 
-            VALUES ($1, $2, $3, $4, $5) 
-            RETURNING id, start_time, end_time, needs_waring, repeats, player_id",
-        )
-        .bind(block.start_time)
-        .bind(block.end_time)
-        .bind(block.need_warning)
-        .bind(block.repeats.to_string())
-        .bind(block.player_id)
+        sqlx::query_as!(
+            RawAvailableBlock,
+            "INSERT INTO available_blocks (start_time, end_time, need_warning, repeats, player_id, timezone)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, start_time, end_time, need_warning, repeats, player_id, timezone",
+            block.start_time,
+            block.end_time,
+            block.need_warning,
+            block.repeats.to_string(),
+            block.player_id,
+            block.timezone.name(),
+        )
         .fetch_one(&self.pool)
         .await
+        .and_then(decode_block)
     }
 
+    #[instrument(level = "debug", skip(self, blocks), err(level = "debug"))]
+    async fn add_available_blocks(
+        &self,
+        blocks: Vec<AvailableBlock>,
+    ) -> Result<Vec<IdentifiableAvailableBlock>, sqlx::error::Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut added = Vec::with_capacity(blocks.len());
+        for block in blocks {
+            let raw = sqlx::query_as!(
+                RawAvailableBlock,
+                "INSERT INTO available_blocks (start_time, end_time, need_warning, repeats, player_id, timezone)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                RETURNING id, start_time, end_time, need_warning, repeats, player_id, timezone",
+                block.start_time,
+                block.end_time,
+                block.need_warning,
+                block.repeats.to_string(),
+                block.player_id,
+                block.timezone.name(),
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            added.push(decode_block(raw)?);
+        }
+        tx.commit().await?;
+        Ok(added)
+    }
+
+    #[instrument(level = "debug", skip(self, block), err(level = "debug"))]
     async fn update_available_block(
         &self,
         block: IdentifiableAvailableBlock,
     ) -> Result<IdentifiableAvailableBlock, sqlx::error::Error> {
-        sqlx::query_as::<_,IdentifiableAvailableBlock>(
-            "UPDATE available_blocks SET start_time=$1, end_time=$2, needs_waring=$3, repeats=$4, player_id=$5) 
-            WHERE block_id=$6
-            RETURNING id, start_time, end_time, needs_waring, repeats, player_id",
-        )
-        .bind(block.inner_block.start_time)
-        .bind(block.inner_block.end_time)
-        .bind(block.inner_block.need_warning)
-        .bind(block.inner_block.repeats.to_string())
-        .bind(block.inner_block.player_id)
-        .bind(block.id)
+        sqlx::query_as!(
+            RawAvailableBlock,
+            "UPDATE available_blocks SET start_time=$1, end_time=$2, need_warning=$3, repeats=$4, player_id=$5, timezone=$7
+            WHERE id=$6
+            RETURNING id, start_time, end_time, need_warning, repeats, player_id, timezone",
+            block.inner_block.start_time,
+            block.inner_block.end_time,
+            block.inner_block.need_warning,
+            block.inner_block.repeats.to_string(),
+            block.inner_block.player_id,
+            block.id,
+            block.inner_block.timezone.name(),
+        )
         .fetch_one(&self.pool)
         .await
+        .and_then(decode_block)
     }
 
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
     async fn delete_available_block(&self, block_id: i32) -> Result<(), sqlx::error::Error> {
         sqlx::query!("DELETE FROM available_blocks WHERE id=$1", block_id)
         .execute(&self.pool)
         .await?;
         Ok(())
     }
+
+    #[instrument(level = "debug", skip(self), err(level = "debug"))]
+    async fn get_available_block_player_id(
+        &self,
+        block_id: i32,
+    ) -> Result<Option<i32>, sqlx::error::Error> {
+        sqlx::query_scalar!(
+            "SELECT player_id FROM available_blocks WHERE id=$1",
+            block_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
 }
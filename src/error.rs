@@ -1,6 +1,158 @@
+use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
 
+// Postgres SQLSTATE codes we map to client errors
+// https://www.postgresql.org/docs/current/errcodes-appendix.html
+const PG_UNIQUE_VIOLATION: &str = "23505";
+const PG_FOREIGN_KEY_VIOLATION: &str = "23503";
+const PG_NOT_NULL_VIOLATION: &str = "23502";
+const PG_CHECK_VIOLATION: &str = "23514";
+const PG_STRING_DATA_RIGHT_TRUNCATION: &str = "22001";
 
-// #[derive(thiserror::Error)]
-// pub enum Error {
-   
-// }
\ No newline at end of file
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0} not found")]
+    NotFound(&'static str),
+    #[error("{0}")]
+    AlreadyExists(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    Validation(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error(transparent)]
+    JsonRejection(#[from] JsonRejection),
+    #[error(transparent)]
+    PathRejection(#[from] PathRejection),
+    #[error(transparent)]
+    QueryRejection(#[from] QueryRejection),
+    #[error("stored data could not be decoded: {0}")]
+    InvalidStoredData(String),
+    #[error(transparent)]
+    Database(sqlx::Error),
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl Error {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Error::NotFound(_) => StatusCode::NOT_FOUND,
+            Error::AlreadyExists(_) | Error::Conflict(_) => StatusCode::CONFLICT,
+            Error::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Error::Forbidden(_) => StatusCode::FORBIDDEN,
+            Error::JsonRejection(rejection) => rejection.status(),
+            Error::PathRejection(_) | Error::QueryRejection(_) => StatusCode::BAD_REQUEST,
+            Error::InvalidStoredData(_) | Error::Database(_) | Error::Internal(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    /// Stable machine readable code, clients should match on this rather than the detail text
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::NotFound(_) => "not_found",
+            Error::AlreadyExists(_) => "already_exists",
+            Error::Conflict(_) => "conflict",
+            Error::Validation(_) => "validation_failed",
+            Error::Unauthorized(_) => "unauthorized",
+            Error::Forbidden(_) => "forbidden",
+            Error::JsonRejection(_) => "invalid_body",
+            Error::PathRejection(_) => "invalid_path",
+            Error::QueryRejection(_) => "invalid_query",
+            Error::InvalidStoredData(_) => "invalid_stored_data",
+            Error::Database(_) | Error::Internal(_) => "internal_error",
+        }
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(value: sqlx::Error) -> Self {
+        match &value {
+            sqlx::Error::RowNotFound => Error::NotFound("resource"),
+            sqlx::Error::Database(db_error) => match db_error.code().as_deref() {
+                Some(PG_UNIQUE_VIOLATION) => {
+                    Error::AlreadyExists(db_error.message().to_string())
+                }
+                Some(PG_FOREIGN_KEY_VIOLATION) => {
+                    Error::Conflict(db_error.message().to_string())
+                }
+                Some(PG_NOT_NULL_VIOLATION)
+                | Some(PG_CHECK_VIOLATION)
+                | Some(PG_STRING_DATA_RIGHT_TRUNCATION) => {
+                    Error::Validation(db_error.message().to_string())
+                }
+                _ => Error::Database(value),
+            },
+            sqlx::Error::ColumnDecode { index, source } => {
+                Error::InvalidStoredData(format!("column {}: {}", index, source))
+            }
+            _ => Error::Database(value),
+        }
+    }
+}
+
+/// RFC 7807 problem details body
+#[derive(Serialize, Debug)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    pub code: &'static str,
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let detail = match &self {
+            // Don't leak database internals to the client
+            Error::Database(err) => {
+                tracing::error!("database error: {}", err);
+                "an internal error occurred".to_string()
+            }
+            Error::Internal(err) => {
+                tracing::error!("internal error: {}", err);
+                "an internal error occurred".to_string()
+            }
+            Error::JsonRejection(rejection) => rejection.body_text(),
+            Error::PathRejection(rejection) => rejection.body_text(),
+            Error::QueryRejection(rejection) => rejection.body_text(),
+            _ => self.to_string(),
+        };
+        let problem = Problem {
+            problem_type: "about:blank".to_string(),
+            title: status
+                .canonical_reason()
+                .unwrap_or("Unknown Error")
+                .to_string(),
+            status: status.as_u16(),
+            detail,
+            code: self.code(),
+        };
+        let mut response = (
+            status,
+            [(header::CONTENT_TYPE, "application/problem+json")],
+            Json(problem),
+        )
+            .into_response();
+        if let Error::Unauthorized(_) = self {
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                header::HeaderValue::from_static("Bearer"),
+            );
+        }
+        response
+    }
+}
@@ -0,0 +1,955 @@
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::{OffsetComponents, OffsetName};
+
+use crate::availability::{self, resolve_local, Interval};
+use crate::model::*;
+
+pub const CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+const PRODID: &str = "-//Team Availability Coordinator//EN";
+// RFC 5545 3.1, content lines are folded at 75 octets
+const MAX_LINE_OCTETS: usize = 75;
+
+/// Builds an iCalendar document line by line, folding and CRLF endings are applied when it
+/// is finished
+pub struct Calendar {
+    lines: Vec<String>,
+    // The calendar properties before the first component, VTIMEZONEs go right after them
+    header_len: usize,
+    // Every timezone named in a TZID, with the earliest local time written in it
+    timezones: Vec<(chrono_tz::Tz, NaiveDateTime)>,
+}
+
+impl Calendar {
+    pub fn new(name: &str) -> Self {
+        let mut calendar = Calendar {
+            lines: Vec::new(),
+            header_len: 0,
+            timezones: Vec::new(),
+        };
+        calendar.line("BEGIN:VCALENDAR");
+        calendar.line("VERSION:2.0");
+        calendar.line(&format!("PRODID:{}", PRODID));
+        calendar.line("CALSCALE:GREGORIAN");
+        calendar.line("METHOD:PUBLISH");
+        calendar.line(&format!("X-WR-CALNAME:{}", escape_text(name)));
+        calendar.header_len = calendar.lines.len();
+        calendar
+    }
+
+    /// Adds a raw content line, the caller is responsible for escaping values
+    pub fn line(&mut self, line: &str) {
+        self.lines.push(line.to_string());
+    }
+
+    pub fn text(&mut self, name: &str, value: &str) {
+        self.line(&format!("{}:{}", name, escape_text(value)));
+    }
+
+    /// Adds a DATE-TIME property with wall clock times in `tz`. UTC is written in the Z form,
+    /// anything else with a TZID parameter and a VTIMEZONE defining it.
+    pub fn local_times(&mut self, name: &str, tz: chrono_tz::Tz, values: &[NaiveDateTime]) {
+        let formatted: Vec<String> = values
+            .iter()
+            .map(|value| value.format("%Y%m%dT%H%M%S").to_string())
+            .collect();
+        if tz == chrono_tz::UTC {
+            let formatted: Vec<String> = formatted.into_iter().map(|value| value + "Z").collect();
+            self.line(&format!("{}:{}", name, formatted.join(",")));
+            return;
+        }
+        if let Some(earliest) = values.iter().min() {
+            match self.timezones.iter_mut().find(|(used, _)| *used == tz) {
+                Some((_, seen)) => *seen = (*seen).min(*earliest),
+                None => self.timezones.push((tz, *earliest)),
+            }
+        }
+        self.line(&format!("{};TZID={}:{}", name, tz.name(), formatted.join(",")));
+    }
+
+    pub fn finish(mut self) -> String {
+        self.line("END:VCALENDAR");
+        let timezones: Vec<String> = self
+            .timezones
+            .iter()
+            .flat_map(|(tz, earliest)| vtimezone(*tz, earliest.year()))
+            .collect();
+        self.lines.splice(self.header_len..self.header_len, timezones);
+        let mut out = String::new();
+        for line in &self.lines {
+            out.push_str(&fold(line));
+            out.push_str("\r\n");
+        }
+        out
+    }
+}
+
+/// Served as text/calendar so calendar apps accept the feed
+pub struct IcsResponse(pub String);
+
+impl IntoResponse for IcsResponse {
+    fn into_response(self) -> Response {
+        ([(header::CONTENT_TYPE, CONTENT_TYPE)], self.0).into_response()
+    }
+}
+
+pub fn escape_text(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Quoted parameter values can't hold DQUOTE or control characters, a CR or LF would end the line
+pub fn param_value(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !c.is_control())
+        .map(|c| if c == '"' { '\'' } else { c })
+        .collect()
+}
+
+// Splits on char boundaries, continuation lines start with a single space
+fn fold(line: &str) -> String {
+    let mut out = String::with_capacity(line.len() + line.len() / MAX_LINE_OCTETS * 3);
+    let mut octets = 0;
+    for c in line.chars() {
+        let width = c.len_utf8();
+        // Continuation lines lose one octet to the leading space
+        if octets + width > MAX_LINE_OCTETS {
+            out.push_str("\r\n ");
+            octets = 1;
+        }
+        out.push(c);
+        octets += width;
+    }
+    out
+}
+
+pub fn format_utc(dt: DateTime<Utc>) -> String {
+    dt.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+// The UTC offset in effect at a UTC instant, with whether it is daylight time and its name
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ZoneOffset {
+    seconds: i32,
+    daylight: bool,
+    name: String,
+}
+
+fn zone_offset(tz: chrono_tz::Tz, utc: NaiveDateTime) -> ZoneOffset {
+    let offset = tz.offset_from_utc_datetime(&utc);
+    ZoneOffset {
+        seconds: offset.fix().local_minus_utc(),
+        daylight: offset.dst_offset() != Duration::zero(),
+        name: offset.abbreviation().to_string(),
+    }
+}
+
+// A change of offset, `local` is the wall clock time it happens at in the offset before it
+struct Transition {
+    local: NaiveDateTime,
+    from: ZoneOffset,
+    to: ZoneOffset,
+}
+
+fn new_year(year: i32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(year, 1, 1)
+        .unwrap_or_default()
+        .and_time(chrono::NaiveTime::MIN)
+}
+
+// The offset changes in the given years, found a day at a time and narrowed down to the second
+fn transitions(tz: chrono_tz::Tz, years: std::ops::Range<i32>) -> Vec<Transition> {
+    let mut found = Vec::new();
+    let mut day = new_year(years.start);
+    let mut current = zone_offset(tz, day);
+    while day < new_year(years.end) {
+        let next = day + Duration::days(1);
+        if zone_offset(tz, next) != current {
+            let (mut before, mut after) = (day, next);
+            while after - before > Duration::seconds(1) {
+                let middle = before + (after - before) / 2;
+                if zone_offset(tz, middle) == current {
+                    before = middle;
+                } else {
+                    after = middle;
+                }
+            }
+            let to = zone_offset(tz, after);
+            found.push(Transition {
+                local: after + Duration::seconds(current.seconds.into()),
+                from: current,
+                to: to.clone(),
+            });
+            current = to;
+        }
+        day = next;
+    }
+    found
+}
+
+// The yearly rule for a change on the same weekday of the month every year, like the last
+// Sunday of March
+fn yearly_rule(local: NaiveDateTime) -> String {
+    const WEEKDAYS: [&str; 7] = ["MO", "TU", "WE", "TH", "FR", "SA", "SU"];
+    let date = local.date();
+    let weekday = WEEKDAYS[date.weekday().num_days_from_monday() as usize];
+    let week = if (date + Duration::days(7)).month() != date.month() {
+        -1
+    } else {
+        (date.day() as i32 - 1) / 7 + 1
+    };
+    format!("FREQ=YEARLY;BYMONTH={};BYDAY={}{}", date.month(), week, weekday)
+}
+
+fn format_offset(seconds: i32) -> String {
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let seconds = seconds.abs();
+    let (hours, minutes, rest) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if rest == 0 {
+        format!("{}{:02}{:02}", sign, hours, minutes)
+    } else {
+        format!("{}{:02}{:02}{:02}", sign, hours, minutes, rest)
+    }
+}
+
+// Writes the VTIMEZONE for a TZID, starting the year before `year` so the earliest time
+// written is covered. When the next year repeats the same changes on the same weekdays they
+// become yearly rules, otherwise the changes of a few years are listed as they are.
+fn vtimezone(tz: chrono_tz::Tz, year: i32) -> Vec<String> {
+    let mut lines = vec![
+        "BEGIN:VTIMEZONE".to_string(),
+        format!("TZID:{}", tz.name()),
+    ];
+    let observance = |lines: &mut Vec<String>, transition: &Transition, rule: Option<String>| {
+        let kind = if transition.to.daylight { "DAYLIGHT" } else { "STANDARD" };
+        lines.push(format!("BEGIN:{}", kind));
+        lines.push(format!("DTSTART:{}", transition.local.format("%Y%m%dT%H%M%S")));
+        if let Some(rule) = rule {
+            lines.push(format!("RRULE:{}", rule));
+        }
+        lines.push(format!("TZOFFSETFROM:{}", format_offset(transition.from.seconds)));
+        lines.push(format!("TZOFFSETTO:{}", format_offset(transition.to.seconds)));
+        lines.push(format!("TZNAME:{}", escape_text(&transition.to.name)));
+        lines.push(format!("END:{}", kind));
+    };
+    let first_year = transitions(tz, year - 1..year);
+    let second_year = transitions(tz, year..year + 1);
+    let repeats = !first_year.is_empty()
+        && first_year.len() == second_year.len()
+        && first_year.iter().zip(&second_year).all(|(first, second)| {
+            first.from == second.from
+                && first.to == second.to
+                && first.local.time() == second.local.time()
+                && yearly_rule(first.local) == yearly_rule(second.local)
+        });
+    if repeats {
+        for transition in &first_year {
+            observance(&mut lines, transition, Some(yearly_rule(transition.local)));
+        }
+    } else {
+        let listed = transitions(tz, year - 1..year + 3);
+        if listed.is_empty() {
+            // A fixed offset, one observance from the epoch on
+            let offset = zone_offset(tz, new_year(year));
+            let fixed = Transition {
+                local: new_year(1970),
+                from: offset.clone(),
+                to: offset,
+            };
+            observance(&mut lines, &fixed, None);
+        }
+        for transition in &listed {
+            observance(&mut lines, transition, None);
+        }
+    }
+    lines.push("END:VTIMEZONE".to_string());
+    lines
+}
+
+// The date a rule instant falls on in the rule's own timezone, which is the date the block uses
+fn rule_date(block: &AvailableBlock, instant: &DateTime<rrule::Tz>) -> NaiveDate {
+    instant
+        .with_timezone(&block.repeats.get_dt_start().timezone())
+        .date_naive()
+}
+
+// Occurrence dates come from the rule and times from the block, so UNTIL moves to the
+// block's start time on the last date the rule still allows
+fn block_until(block: &AvailableBlock, until: &DateTime<rrule::Tz>) -> Option<DateTime<Utc>> {
+    let rule_start = block.repeats.get_dt_start();
+    let until = until.with_timezone(&rule_start.timezone());
+    let mut last_date = until.date_naive();
+    if until.time() < rule_start.time() {
+        last_date = last_date.pred_opt()?;
+    }
+    resolve_local(&block.timezone, last_date.and_time(block.start_time))
+}
+
+// The rrule crate fills in BYHOUR/BYMINUTE/BYSECOND from the rule's DTSTART, those would
+// move every instance off the block's times so they are left out
+fn block_rule(block: &AvailableBlock, rule: &rrule::RRule) -> String {
+    let until = rule.get_until().and_then(|until| block_until(block, until));
+    rule.to_string()
+        .split(';')
+        .filter(|part| {
+            !["BYHOUR=", "BYMINUTE=", "BYSECOND="]
+                .iter()
+                .any(|prefix| part.starts_with(prefix))
+        })
+        .map(|part| match until {
+            Some(until) if part.starts_with("UNTIL=") => format!("UNTIL={}", format_utc(until)),
+            _ => part.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+// RFC 5545 counts DTSTART as the first instance, but a rule's DTSTART doesn't have to match
+// the rule. The first date the rules produce is used instead, or the first RDATE without
+// rules. EXDATEs stay out of it so COUNT keeps counting from the same instance.
+fn first_date(block: &AvailableBlock) -> NaiveDate {
+    let repeats = &block.repeats;
+    let rules = rrule::RRuleSet::new(*repeats.get_dt_start());
+    let rules = if repeats.get_rrule().is_empty() {
+        rules.set_rdates(repeats.get_rdate().clone())
+    } else {
+        rules.set_rrules(repeats.get_rrule().clone())
+    };
+    let first = rules.all(1).dates.into_iter().next();
+    rule_date(block, first.as_ref().unwrap_or(repeats.get_dt_start()))
+}
+
+/// Writes the block as one recurring VEVENT. DTSTART is the block's start time on the first
+/// date of its rule, EXDATE and RDATE are moved onto the block's start time the same way.
+pub fn block_event(
+    calendar: &mut Calendar,
+    block: &IdentifiableAvailableBlock,
+    now: DateTime<Utc>,
+) {
+    let inner = &block.inner_block;
+    let first_date = first_date(inner);
+    let end_date = if inner.is_overnight() {
+        first_date.succ_opt().unwrap_or(first_date)
+    } else {
+        first_date
+    };
+
+    calendar.line("BEGIN:VEVENT");
+    calendar.line(&format!(
+        "UID:available-block-{}@team-availability",
+        block.id
+    ));
+    calendar.line(&format!("DTSTAMP:{}", format_utc(now)));
+    calendar.local_times(
+        "DTSTART",
+        inner.timezone,
+        &[first_date.and_time(inner.start_time)],
+    );
+    calendar.local_times(
+        "DTEND",
+        inner.timezone,
+        &[end_date.and_time(inner.end_time)],
+    );
+    for rule in inner.repeats.get_rrule() {
+        calendar.line(&format!("RRULE:{}", block_rule(inner, rule)));
+    }
+    for rule in inner.repeats.get_exrule() {
+        calendar.line(&format!("EXRULE:{}", block_rule(inner, rule)));
+    }
+    let shifted = |dates: &Vec<DateTime<rrule::Tz>>| -> Vec<NaiveDateTime> {
+        dates
+            .iter()
+            .map(|date| rule_date(inner, date).and_time(inner.start_time))
+            .collect()
+    };
+    let exdates = shifted(inner.repeats.get_exdate());
+    if !exdates.is_empty() {
+        calendar.local_times("EXDATE", inner.timezone, &exdates);
+    }
+    let rdates = shifted(inner.repeats.get_rdate());
+    if !rdates.is_empty() {
+        calendar.local_times("RDATE", inner.timezone, &rdates);
+    }
+    calendar.text("SUMMARY", "Available");
+    if inner.need_warning {
+        calendar.text("DESCRIPTION", "Ask before scheduling");
+    }
+    calendar.line("TRANSP:TRANSPARENT");
+    calendar.line("END:VEVENT");
+}
+
+/// Writes a window where the whole roster is available. The UID comes from the start so a
+/// window keeps its identity between fetches while it doesn't move.
+pub fn overlap_event(
+    calendar: &mut Calendar,
+    team_id: i32,
+    interval: &Interval,
+    now: DateTime<Utc>,
+) {
+    calendar.line("BEGIN:VEVENT");
+    calendar.line(&format!(
+        "UID:team-{}-{}@team-availability",
+        team_id,
+        interval.start.timestamp()
+    ));
+    calendar.line(&format!("DTSTAMP:{}", format_utc(now)));
+    calendar.line(&format!("DTSTART:{}", format_utc(interval.start)));
+    calendar.line(&format!("DTEND:{}", format_utc(interval.end)));
+    calendar.text("SUMMARY", "Everyone available");
+    calendar.line("TRANSP:TRANSPARENT");
+    calendar.line("END:VEVENT");
+}
+
+/// Writes a VFREEBUSY for `[from, to)`. The free intervals are listed as FBTYPE=FREE and the
+/// rest of the range as BUSY, tools that only read busy time see the same picture.
+pub fn freebusy(
+    calendar: &mut Calendar,
+    uid: &str,
+    attendee: Option<(i32, &str)>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    free: &[Interval],
+    now: DateTime<Utc>,
+) {
+    let period = |interval: &Interval| {
+        format!("{}/{}", format_utc(interval.start), format_utc(interval.end))
+    };
+    calendar.line("BEGIN:VFREEBUSY");
+    calendar.line(&format!("UID:{}@team-availability", uid));
+    calendar.line(&format!("DTSTAMP:{}", format_utc(now)));
+    calendar.line(&format!("DTSTART:{}", format_utc(from)));
+    calendar.line(&format!("DTEND:{}", format_utc(to)));
+    if let Some((player_id, name)) = attendee {
+        calendar.line(&format!(
+            "ATTENDEE;CN=\"{}\":urn:team-availability:player:{}",
+            param_value(name),
+            player_id
+        ));
+    }
+    for interval in free {
+        calendar.line(&format!("FREEBUSY;FBTYPE=FREE:{}", period(interval)));
+    }
+    for interval in availability::complement(free, from, to) {
+        calendar.line(&format!("FREEBUSY;FBTYPE=BUSY:{}", period(&interval)));
+    }
+    calendar.line("END:VFREEBUSY");
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ParseError {
+    #[error("not an iCalendar file, it must start with BEGIN:VCALENDAR")]
+    NotACalendar,
+    #[error("line {0} is not a valid content line")]
+    Malformed(usize),
+    #[error("{0} is missing")]
+    Missing(&'static str),
+    #[error("{0} has an invalid value `{1}`")]
+    InvalidValue(String, String),
+    #[error("unknown timezone `{0}`")]
+    Timezone(String),
+}
+
+/// One content line of an uploaded calendar, names are uppercased
+#[derive(Debug, Clone)]
+pub struct Property {
+    pub name: String,
+    pub params: Vec<(String, String)>,
+    pub value: String,
+}
+
+impl Property {
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn invalid(&self) -> ParseError {
+        ParseError::InvalidValue(self.name.clone(), self.value.clone())
+    }
+}
+
+// Splits on `separator` outside of double quoted parameter values
+fn split_unquoted(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (index, c) in value.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            parts.push(&value[start..index]);
+            start = index + 1;
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+fn parse_line(line: &str) -> Option<Property> {
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(index, c)| {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        (c == ':' && !quoted).then_some(index)
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let mut parts = split_unquoted(head, ';').into_iter();
+    let name = parts.next()?.trim();
+    if name.is_empty() {
+        return None;
+    }
+    let params = parts
+        .map(|param| {
+            let (name, value) = param.split_once('=')?;
+            Some((name.to_uppercase(), value.trim_matches('"').to_string()))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(Property {
+        name: name.to_uppercase(),
+        params,
+        value: value.to_string(),
+    })
+}
+
+// Joins folded lines back together, tolerating bare LF line endings
+fn unfold(text: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (index, line) in text.split('\n').enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (
+            line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')),
+            lines.last_mut(),
+        ) {
+            (Some(continuation), Some((_, last))) => last.push_str(continuation),
+            _ if line.trim().is_empty() => {}
+            _ => lines.push((index + 1, line.to_string())),
+        }
+    }
+    lines
+}
+
+pub fn unescape_text(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, c == '\\') {
+            (_, true) => match chars.next() {
+                Some('n') | Some('N') => out.push('\n'),
+                Some(other) => out.push(other),
+                None => {}
+            },
+            (c, false) => out.push(c),
+        }
+    }
+    out
+}
+
+/// A VEVENT as uploaded, kept unparsed so an event that can't be read can still be reported
+/// by its UID and SUMMARY
+#[derive(Debug, Clone)]
+pub struct RawEvent {
+    pub uid: Option<String>,
+    pub summary: Option<String>,
+    pub properties: Vec<Property>,
+}
+
+/// The top level VEVENTs of a calendar, nested components like VALARM are left out
+pub fn parse_events(text: &str) -> Result<Vec<RawEvent>, ParseError> {
+    let lines = unfold(text.trim_start_matches('\u{feff}'));
+    let mut components: Vec<String> = Vec::new();
+    let mut events = Vec::new();
+    let mut current: Vec<Property> = Vec::new();
+    for (number, line) in lines {
+        let property = parse_line(&line).ok_or(ParseError::Malformed(number))?;
+        if components.is_empty()
+            && !(property.name == "BEGIN" && property.value.eq_ignore_ascii_case("VCALENDAR"))
+        {
+            return Err(ParseError::NotACalendar);
+        }
+        match property.name.as_str() {
+            "BEGIN" => components.push(property.value.to_uppercase()),
+            "END" => {
+                let ended = components.pop();
+                if ended.as_deref() != Some("VEVENT") || components.len() != 1 {
+                    continue;
+                }
+                let properties = std::mem::take(&mut current);
+                let text = |name: &str| {
+                    properties
+                        .iter()
+                        .find(|property| property.name == name)
+                        .map(|property| unescape_text(&property.value))
+                };
+                events.push(RawEvent {
+                    uid: text("UID"),
+                    summary: text("SUMMARY"),
+                    properties,
+                });
+            }
+            _ if components.len() == 2 && components[1] == "VEVENT" => current.push(property),
+            _ => {}
+        }
+    }
+    if !components.is_empty() {
+        return Err(ParseError::Missing("END:VCALENDAR"));
+    }
+    Ok(events)
+}
+
+/// A DATE or DATE-TIME value in any of the forms RFC 5545 allows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IcsTime {
+    Date(NaiveDate),
+    Utc(DateTime<Utc>),
+    Local(NaiveDateTime, chrono_tz::Tz),
+    // No timezone at all, read in whatever timezone the importer picked
+    Floating(NaiveDateTime),
+}
+
+impl IcsTime {
+    /// The wall clock time in `tz`, dates start at midnight
+    pub fn local(&self, tz: chrono_tz::Tz) -> NaiveDateTime {
+        match self {
+            IcsTime::Date(date) => date.and_time(chrono::NaiveTime::MIN),
+            IcsTime::Utc(dt) => dt.with_timezone(&tz).naive_local(),
+            IcsTime::Local(local, own) if *own == tz => *local,
+            IcsTime::Local(local, own) => resolve_local(own, *local)
+                .map(|dt| dt.with_timezone(&tz).naive_local())
+                .unwrap_or(*local),
+            IcsTime::Floating(local) => *local,
+        }
+    }
+
+    /// The instant this is, floating times and dates are read in `tz`
+    pub fn resolve(&self, tz: chrono_tz::Tz) -> Option<DateTime<Utc>> {
+        match self {
+            IcsTime::Utc(dt) => Some(*dt),
+            IcsTime::Local(local, own) => resolve_local(own, *local),
+            IcsTime::Date(_) | IcsTime::Floating(_) => resolve_local(&tz, self.local(tz)),
+        }
+    }
+
+    pub fn is_date(&self) -> bool {
+        matches!(self, IcsTime::Date(_))
+    }
+}
+
+/// A single value without a TZID parameter, like the UNTIL of a rule
+pub fn parse_time(value: &str) -> Option<IcsTime> {
+    parse_time_value(value, None)
+}
+
+fn parse_time_value(value: &str, tz: Option<chrono_tz::Tz>) -> Option<IcsTime> {
+    if value.len() == 8 {
+        return NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()
+            .map(IcsTime::Date);
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        let local = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(IcsTime::Utc(Utc.from_utc_datetime(&local)));
+    }
+    let local = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    Some(match tz {
+        Some(tz) => IcsTime::Local(local, tz),
+        None => IcsTime::Floating(local),
+    })
+}
+
+/// The comma separated values of a DTSTART, DTEND, EXDATE or RDATE property
+pub fn parse_times(property: &Property) -> Result<Vec<IcsTime>, ParseError> {
+    if property
+        .param("VALUE")
+        .is_some_and(|value| value.eq_ignore_ascii_case("PERIOD"))
+    {
+        return Err(property.invalid());
+    }
+    let tz = match property.param("TZID") {
+        // Some exporters prefix the name with a slash
+        Some(name) => Some(
+            name.trim_start_matches('/')
+                .parse::<chrono_tz::Tz>()
+                .map_err(|_| ParseError::Timezone(name.to_string()))?,
+        ),
+        None => None,
+    };
+    property
+        .value
+        .split(',')
+        .map(|value| parse_time_value(value.trim(), tz).ok_or_else(|| property.invalid()))
+        .collect()
+}
+
+/// A DURATION such as `PT1H30M` or `P1D`
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let value = value.strip_prefix('P')?;
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' if number.is_empty() => in_time = true,
+            unit => {
+                let amount: i64 = number.parse().ok()?;
+                number.clear();
+                total += match (unit, in_time) {
+                    ('W', false) => Duration::weeks(amount),
+                    ('D', false) => Duration::days(amount),
+                    ('H', true) => Duration::hours(amount),
+                    ('M', true) => Duration::minutes(amount),
+                    ('S', true) => Duration::seconds(amount),
+                    _ => return None,
+                };
+            }
+        }
+    }
+    if !number.is_empty() {
+        return None;
+    }
+    Some(if negative { -total } else { total })
+}
+
+/// The parts of a VEVENT the importer understands
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub start: IcsTime,
+    pub end: Option<IcsTime>,
+    pub duration: Option<Duration>,
+    // Kept as written, UNTIL is normalized by the importer
+    pub rrules: Vec<String>,
+    pub rdates: Vec<IcsTime>,
+    pub exdates: Vec<IcsTime>,
+    // Set on an event that replaces one instance of a recurring event with the same UID
+    pub recurrence_id: Option<IcsTime>,
+    // TRANSP:TRANSPARENT, the event doesn't block time
+    pub transparent: bool,
+    pub cancelled: bool,
+}
+
+impl RawEvent {
+    pub fn parse(&self) -> Result<Event, ParseError> {
+        let single = |property: &Property| -> Result<IcsTime, ParseError> {
+            match parse_times(property)?.as_slice() {
+                [time] => Ok(*time),
+                _ => Err(property.invalid()),
+            }
+        };
+        let mut start = None;
+        let mut end = None;
+        let mut duration = None;
+        let mut rrules = Vec::new();
+        let mut rdates = Vec::new();
+        let mut exdates = Vec::new();
+        let mut recurrence_id = None;
+        let mut transparent = false;
+        let mut cancelled = false;
+        for property in &self.properties {
+            match property.name.as_str() {
+                "DTSTART" => start = Some(single(property)?),
+                "DTEND" => end = Some(single(property)?),
+                "DURATION" => {
+                    duration =
+                        Some(parse_duration(&property.value).ok_or_else(|| property.invalid())?)
+                }
+                "RRULE" => rrules.push(property.value.clone()),
+                "RDATE" => rdates.extend(parse_times(property)?),
+                "EXDATE" => exdates.extend(parse_times(property)?),
+                "RECURRENCE-ID" => recurrence_id = Some(single(property)?),
+                "TRANSP" => transparent = property.value.eq_ignore_ascii_case("TRANSPARENT"),
+                "STATUS" => cancelled = property.value.eq_ignore_ascii_case("CANCELLED"),
+                _ => {}
+            }
+        }
+        Ok(Event {
+            start: start.ok_or(ParseError::Missing("DTSTART"))?,
+            end,
+            duration,
+            rrules,
+            rdates,
+            exdates,
+            recurrence_id,
+            transparent,
+            cancelled,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fold_splits_long_lines_on_char_boundaries() {
+        assert_eq!(fold("SUMMARY:Practice"), "SUMMARY:Practice");
+
+        let line = format!("SUMMARY:{}", "a".repeat(100));
+        let folded = fold(&line);
+        let parts: Vec<&str> = folded.split("\r\n").collect();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].len(), MAX_LINE_OCTETS);
+        assert!(parts[1].starts_with(' '));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+
+        // A two octet char that would straddle the limit moves to the next line
+        let line = format!("{}é", "a".repeat(MAX_LINE_OCTETS - 1));
+        let folded = fold(&line);
+        assert_eq!(folded, format!("{}\r\n é", "a".repeat(MAX_LINE_OCTETS - 1)));
+    }
+
+    #[test]
+    fn unfold_joins_continuations() {
+        let lines = unfold("BEGIN:VCALENDAR\r\nSUMMARY:Long\r\n  practice\n\tnight\r\n\r\nEND:VCALENDAR");
+        assert_eq!(
+            lines,
+            vec![
+                (1, "BEGIN:VCALENDAR".to_string()),
+                (2, "SUMMARY:Long practicenight".to_string()),
+                (6, "END:VCALENDAR".to_string()),
+            ]
+        );
+        // Folding and unfolding gives the line back
+        let line = format!("DESCRIPTION:{}", "ü".repeat(60));
+        assert_eq!(unfold(&fold(&line)), vec![(1, line)]);
+    }
+
+    #[test]
+    fn attendee_names_stay_in_their_parameter() {
+        let from = Utc.with_ymd_and_hms(2026, 10, 19, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2026, 10, 20, 0, 0, 0).unwrap();
+        let mut calendar = Calendar::new("falcons free/busy");
+        freebusy(
+            &mut calendar,
+            "player-1-freebusy",
+            Some((1, "eve\"\r\nBEGIN:VEVENT\r\nSUMMARY:x")),
+            from,
+            to,
+            &[],
+            from,
+        );
+        let text = calendar.finish();
+        let lines: Vec<&str> = text.split("\r\n").collect();
+        assert!(!lines.contains(&"BEGIN:VEVENT"), "{}", text);
+        assert!(lines.contains(
+            &"ATTENDEE;CN=\"eve'BEGIN:VEVENTSUMMARY:x\":urn:team-availability:player:1"
+        ));
+        assert_eq!(param_value("tab\there\u{7f}"), "tabhere");
+    }
+
+    #[test]
+    fn parse_line_reads_names_params_and_values() {
+        let property =
+            parse_line(r#"dtstart;tzid="Europe/Berlin";VALUE=DATE-TIME:20261019T180000"#).unwrap();
+        assert_eq!(property.name, "DTSTART");
+        assert_eq!(property.param("TZID"), Some("Europe/Berlin"));
+        assert_eq!(property.param("value"), Some("DATE-TIME"));
+        assert_eq!(property.value, "20261019T180000");
+
+        // Colons and semicolons inside quotes belong to the parameter
+        let property = parse_line(r#"ATTENDEE;CN="Doe; Jane: captain":mailto:jane@example.com"#)
+            .unwrap();
+        assert_eq!(property.param("CN"), Some("Doe; Jane: captain"));
+        assert_eq!(property.value, "mailto:jane@example.com");
+
+        assert!(parse_line("SUMMARY").is_none());
+        assert!(parse_line(":value").is_none());
+        assert!(parse_line("DTSTART;TZID:20261019T180000").is_none());
+    }
+
+    #[test]
+    fn parse_duration_forms() {
+        assert_eq!(parse_duration("PT1H30M"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("P1D"), Some(Duration::days(1)));
+        assert_eq!(parse_duration("P2W"), Some(Duration::weeks(2)));
+        assert_eq!(
+            parse_duration("P1DT2H3M4S"),
+            Some(Duration::days(1) + Duration::hours(2) + Duration::minutes(3) + Duration::seconds(4))
+        );
+        assert_eq!(parse_duration("-PT15M"), Some(Duration::minutes(-15)));
+        assert_eq!(parse_duration("+PT15M"), Some(Duration::minutes(15)));
+        for invalid in ["", "1H", "P1H", "PT1D", "PT1", "P1.5D", "PTH"] {
+            assert_eq!(parse_duration(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn parse_time_forms() {
+        let date = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let local = date.and_hms_opt(18, 0, 0).unwrap();
+        assert_eq!(parse_time("20261019"), Some(IcsTime::Date(date)));
+        assert_eq!(
+            parse_time("20261019T180000Z"),
+            Some(IcsTime::Utc(Utc.from_utc_datetime(&local)))
+        );
+        assert_eq!(parse_time("20261019T180000"), Some(IcsTime::Floating(local)));
+        assert_eq!(parse_time("2026-10-19"), None);
+
+        let property = parse_line("EXDATE;TZID=/Europe/Berlin:20261019T180000,20261020T180000").unwrap();
+        let times = parse_times(&property).unwrap();
+        assert_eq!(times.len(), 2);
+        assert_eq!(times[0], IcsTime::Local(local, chrono_tz::Europe::Berlin));
+        let property = parse_line("DTSTART;TZID=Mars/Olympus:20261019T180000").unwrap();
+        assert!(matches!(parse_times(&property), Err(ParseError::Timezone(_))));
+    }
+
+    #[test]
+    fn vtimezone_for_a_fixed_offset() {
+        assert_eq!(format_offset(-9000), "-0230");
+        assert_eq!(format_offset(19800), "+0530");
+        assert_eq!(format_offset(3601), "+010001");
+        assert_eq!(
+            vtimezone(chrono_tz::Asia::Kolkata, 2026),
+            vec![
+                "BEGIN:VTIMEZONE",
+                "TZID:Asia/Kolkata",
+                "BEGIN:STANDARD",
+                "DTSTART:19700101T000000",
+                "TZOFFSETFROM:+0530",
+                "TZOFFSETTO:+0530",
+                "TZNAME:IST",
+                "END:STANDARD",
+                "END:VTIMEZONE",
+            ]
+        );
+    }
+
+    #[test]
+    fn vtimezone_rules_follow_the_weekday() {
+        // New York changes on the second Sunday of March and the first Sunday of November
+        let lines = vtimezone(chrono_tz::America::New_York, 2026);
+        assert!(lines.contains(&"DTSTART:20250309T020000".to_string()), "{:?}", lines);
+        assert!(lines.contains(&"RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=2SU".to_string()));
+        assert!(lines.contains(&"DTSTART:20251102T020000".to_string()), "{:?}", lines);
+        assert!(lines.contains(&"RRULE:FREQ=YEARLY;BYMONTH=11;BYDAY=1SU".to_string()));
+        assert!(lines.contains(&"TZOFFSETTO:-0400".to_string()));
+    }
+
+    #[test]
+    fn text_escaping_round_trips() {
+        let text = "Court 2; bring water, shoes\\spikes\nsee you";
+        let escaped = escape_text(text);
+        assert_eq!(escaped, r"Court 2\; bring water\, shoes\\spikes\nsee you");
+        assert_eq!(unescape_text(&escaped), text);
+    }
+}
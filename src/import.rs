@@ -0,0 +1,505 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use serde::Serialize;
+
+use crate::availability::{resolve_local, Interval};
+use crate::ical::{self, Event, IcsTime, RawEvent};
+use crate::model::*;
+
+// Upper bound on occurrences expanded per imported event, keeps a bad rule from spinning
+const MAX_OCCURRENCES_PER_EVENT: u16 = 2000;
+// Gaps between busy events shorter than this don't become blocks
+const MIN_FREE_MINUTES: i64 = 30;
+
+// An event that didn't become a block, with the reason shown to the player
+#[derive(Serialize, Debug, Clone)]
+pub struct SkippedEvent {
+    pub uid: Option<String>,
+    pub summary: Option<String>,
+    pub reason: String,
+}
+
+impl SkippedEvent {
+    fn new(event: &RawEvent, reason: impl Into<String>) -> Self {
+        SkippedEvent {
+            uid: event.uid.clone(),
+            summary: event.summary.clone(),
+            reason: reason.into(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResult<B> {
+    // Nothing was written, `blocks` is what would be created
+    pub dry_run: bool,
+    pub blocks: Vec<B>,
+    pub skipped: Vec<SkippedEvent>,
+}
+
+/// The part of each day the player could play, busy time is cut out of it
+#[derive(Debug, Clone, Copy)]
+pub struct WakingHours {
+    pub start: NaiveTime,
+    // Before start for windows that run past midnight
+    pub end: NaiveTime,
+}
+
+// Parses every event, events replacing one instance of a series are taken out of the series
+// so the instance isn't counted twice
+fn parse_events(events: &[RawEvent]) -> (Vec<(&RawEvent, Event)>, Vec<SkippedEvent>) {
+    let mut parsed = Vec::new();
+    let mut skipped = Vec::new();
+    for raw in events {
+        match raw.parse() {
+            Ok(event) if event.cancelled => {
+                skipped.push(SkippedEvent::new(raw, "event is cancelled"))
+            }
+            Ok(event) => parsed.push((raw, event)),
+            Err(err) => skipped.push(SkippedEvent::new(raw, err.to_string())),
+        }
+    }
+    let overrides: Vec<(String, IcsTime)> = parsed
+        .iter()
+        .filter_map(|(raw, event)| Some((raw.uid.clone()?, event.recurrence_id?)))
+        .collect();
+    for (raw, event) in parsed.iter_mut() {
+        if event.recurrence_id.is_some() {
+            continue;
+        }
+        event.exdates.extend(
+            overrides
+                .iter()
+                .filter(|(uid, _)| raw.uid.as_ref() == Some(uid))
+                .map(|(_, recurrence_id)| *recurrence_id),
+        );
+    }
+    (parsed, skipped)
+}
+
+// From DTEND, DURATION or the RFC 5545 defaults of one day for dates and none for times
+fn event_duration(event: &Event, tz: chrono_tz::Tz) -> Option<Duration> {
+    match (event.end, event.duration) {
+        (Some(end), _) => Some(end.resolve(tz)? - event.start.resolve(tz)?),
+        (None, Some(duration)) => Some(duration),
+        (None, None) if event.start.is_date() => Some(Duration::days(1)),
+        (None, None) => Some(Duration::zero()),
+    }
+}
+
+fn utc_date(date: NaiveDate) -> String {
+    date.format("%Y%m%dT000000Z").to_string()
+}
+
+fn date_list(dates: &[NaiveDate]) -> String {
+    dates
+        .iter()
+        .map(|date| utc_date(*date))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// Rewrites the UNTIL part of a rule, leaving the rest as written
+fn rewrite_until(
+    rule: &str,
+    rewrite: impl Fn(IcsTime) -> Result<String, String>,
+) -> Result<String, String> {
+    rule.split(';')
+        .map(|part| match part.split_once('=') {
+            Some((name, value)) if name.eq_ignore_ascii_case("UNTIL") => {
+                let until = ical::parse_time(value)
+                    .ok_or_else(|| format!("RRULE has an invalid UNTIL `{}`", value))?;
+                Ok(format!("UNTIL={}", rewrite(until)?))
+            }
+            _ => Ok(part.to_string()),
+        })
+        .collect::<Result<Vec<_>, String>>()
+        .map(|parts| parts.join(";"))
+}
+
+// Blocks only take dates from their rule, so the event's rule is rewritten onto midnight UTC
+// of each local date. UNTIL becomes the end of its local date and the time of day parts go.
+fn date_rule(
+    event: &Event,
+    tz: chrono_tz::Tz,
+    first_date: NaiveDate,
+) -> Result<MyRRuleSet, String> {
+    let mut lines = vec![format!("DTSTART:{}", utc_date(first_date))];
+    for rule in &event.rrules {
+        let rule = rewrite_until(rule, |until| {
+            Ok(until.local(tz).date().format("%Y%m%dT235959Z").to_string())
+        })?;
+        let parts: Vec<&str> = rule
+            .split(';')
+            .filter(|part| {
+                !["BYHOUR=", "BYMINUTE=", "BYSECOND="]
+                    .iter()
+                    .any(|prefix| part.to_uppercase().starts_with(prefix))
+            })
+            .collect();
+        lines.push(format!("RRULE:{}", parts.join(";")));
+    }
+    let local_dates = |times: &[IcsTime]| -> Vec<NaiveDate> {
+        times.iter().map(|time| time.local(tz).date()).collect()
+    };
+    let mut rdates = local_dates(&event.rdates);
+    // Without a rule DTSTART is the only instance, the rule set needs it as an RDATE
+    if event.rrules.is_empty() {
+        rdates.insert(0, first_date);
+    }
+    if !rdates.is_empty() {
+        lines.push(format!("RDATE:{}", date_list(&rdates)));
+    }
+    let exdates = local_dates(&event.exdates);
+    if !exdates.is_empty() {
+        lines.push(format!("EXDATE:{}", date_list(&exdates)));
+    }
+    MyRRuleSet::try_from(lines.join("\n")).map_err(|err| err.to_string())
+}
+
+fn event_block(
+    event: &Event,
+    player_id: i32,
+    default_tz: chrono_tz::Tz,
+) -> Result<AvailableBlock, String> {
+    let (start, tz) = match event.start {
+        IcsTime::Date(_) => return Err("all-day events have no times to import".to_string()),
+        // Moved into the importer's timezone unless that changes the date a rule's BYDAY and
+        // friends were written against
+        IcsTime::Utc(dt) => {
+            let local = dt.with_timezone(&default_tz).naive_local();
+            if event.rrules.is_empty() || local.date() == dt.date_naive() {
+                (local, default_tz)
+            } else {
+                (dt.naive_utc(), chrono_tz::UTC)
+            }
+        }
+        IcsTime::Local(local, tz) => (local, tz),
+        IcsTime::Floating(local) => (local, default_tz),
+    };
+    let duration = event_duration(event, tz).ok_or("event times don't exist in its timezone")?;
+    if duration <= Duration::zero() {
+        return Err("event must end after it starts".to_string());
+    }
+    if duration >= Duration::days(1) {
+        return Err("event lasts a day or longer, blocks cover less than a day".to_string());
+    }
+    let end_time = match event.end {
+        Some(end) => end.local(tz).time(),
+        None => (start + duration).time(),
+    };
+    if end_time == start.time() {
+        return Err("event must end after it starts".to_string());
+    }
+    Ok(AvailableBlock {
+        start_time: start.time(),
+        end_time,
+        need_warning: false,
+        repeats: date_rule(event, tz, start.date())?,
+        player_id,
+        timezone: tz,
+    })
+}
+
+/// Turns each event into a block with the event's times and recurrence. Floating times are
+/// read in `tz`.
+pub fn blocks_from_events(
+    events: &[RawEvent],
+    player_id: i32,
+    tz: chrono_tz::Tz,
+) -> (Vec<AvailableBlock>, Vec<SkippedEvent>) {
+    let (parsed, mut skipped) = parse_events(events);
+    let mut blocks = Vec::new();
+    for (raw, event) in parsed {
+        match event_block(&event, player_id, tz) {
+            Ok(block) => blocks.push(block),
+            Err(reason) => skipped.push(SkippedEvent::new(raw, reason)),
+        }
+    }
+    (blocks, skipped)
+}
+
+// DTSTART, EXDATE and RDATE for the rrule crate, which needs every time tied to a timezone
+fn rule_time(name: &str, time: &IcsTime, tz: chrono_tz::Tz) -> String {
+    let (tz, local) = match time {
+        IcsTime::Utc(dt) => return format!("{}:{}", name, dt.format("%Y%m%dT%H%M%SZ")),
+        IcsTime::Local(local, own) => (*own, *local),
+        IcsTime::Date(_) | IcsTime::Floating(_) => (tz, time.local(tz)),
+    };
+    if tz == chrono_tz::UTC {
+        format!("{}:{}", name, local.format("%Y%m%dT%H%M%SZ"))
+    } else {
+        format!(
+            "{};TZID={}:{}",
+            name,
+            tz.name(),
+            local.format("%Y%m%dT%H%M%S")
+        )
+    }
+}
+
+// UNTIL has to be in UTC once DTSTART has a timezone, a date means the end of that day
+fn utc_until(rule: &str, tz: chrono_tz::Tz) -> Result<String, String> {
+    rewrite_until(rule, |until| {
+        let instant = match until {
+            IcsTime::Date(date) => resolve_local(&tz, date.and_hms_opt(23, 59, 59).unwrap()),
+            other => other.resolve(tz),
+        }
+        .ok_or("UNTIL doesn't exist in the event's timezone")?;
+        Ok(instant.format("%Y%m%dT%H%M%SZ").to_string())
+    })
+}
+
+// The start of every instance of the event within `[from, to]`
+fn event_starts(
+    event: &Event,
+    tz: chrono_tz::Tz,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<DateTime<Utc>>, String> {
+    if event.rrules.is_empty() {
+        let excluded: Vec<DateTime<Utc>> = event
+            .exdates
+            .iter()
+            .filter_map(|time| time.resolve(tz))
+            .collect();
+        return Ok(std::iter::once(&event.start)
+            .chain(&event.rdates)
+            .filter_map(|start| start.resolve(tz))
+            .filter(|start| !excluded.contains(start))
+            .filter(|start| *start >= from && *start <= to)
+            .collect());
+    }
+    let mut lines = vec![rule_time("DTSTART", &event.start, tz)];
+    for rule in &event.rrules {
+        lines.push(format!("RRULE:{}", utc_until(rule, tz)?));
+    }
+    lines.extend(event.rdates.iter().map(|time| rule_time("RDATE", time, tz)));
+    lines.extend(
+        event
+            .exdates
+            .iter()
+            .map(|time| rule_time("EXDATE", time, tz)),
+    );
+    let set: rrule::RRuleSet = lines
+        .join("\n")
+        .parse()
+        .map_err(|err: rrule::RRuleError| err.to_string())?;
+    Ok(set
+        .after(from.with_timezone(&rrule::Tz::UTC))
+        .before(to.with_timezone(&rrule::Tz::UTC))
+        .all(MAX_OCCURRENCES_PER_EVENT)
+        .dates
+        .into_iter()
+        .map(|start| start.with_timezone(&Utc))
+        .collect())
+}
+
+/// Treats the events as busy time and creates blocks from what is left of the waking hours on
+/// each date from `from` up to `to`, in `tz`. Windows with the same times share one block.
+pub fn blocks_from_busy_time(
+    events: &[RawEvent],
+    player_id: i32,
+    tz: chrono_tz::Tz,
+    waking: WakingHours,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> (Vec<AvailableBlock>, Vec<SkippedEvent>) {
+    let first_date = from.with_timezone(&tz).date_naive();
+    let last_date = to
+        .with_timezone(&tz)
+        .date_naive()
+        .max(first_date.succ_opt().unwrap_or(first_date));
+    let overnight = waking.end <= waking.start;
+    let windows: Vec<Interval> = first_date
+        .iter_days()
+        .take_while(|date| *date < last_date)
+        .filter_map(|date| {
+            let end_date = if overnight { date.succ_opt()? } else { date };
+            Some(Interval {
+                start: resolve_local(&tz, date.and_time(waking.start))?,
+                end: resolve_local(&tz, end_date.and_time(waking.end))?,
+            })
+        })
+        .collect();
+    let (Some(first), Some(last)) = (windows.first(), windows.last()) else {
+        return (Vec::new(), Vec::new());
+    };
+    let (span_start, span_end) = (first.start, last.end);
+
+    let (parsed, mut skipped) = parse_events(events);
+    let mut busy: Vec<Interval> = Vec::new();
+    for (raw, event) in parsed {
+        if event.transparent {
+            skipped.push(SkippedEvent::new(raw, "event is marked as free"));
+            continue;
+        }
+        let Some(duration) = event_duration(&event, tz) else {
+            skipped.push(SkippedEvent::new(
+                raw,
+                "event times don't exist in its timezone",
+            ));
+            continue;
+        };
+        // Instances starting before the span can still run into it
+        match event_starts(
+            &event,
+            tz,
+            span_start - duration.max(Duration::zero()),
+            span_end,
+        ) {
+            Ok(starts) => busy.extend(starts.into_iter().map(|start| Interval {
+                start,
+                end: start + duration,
+            })),
+            Err(reason) => skipped.push(SkippedEvent::new(raw, reason)),
+        }
+    }
+    busy.sort_by_key(|interval| interval.start);
+
+    // Local start and end times to the dates that have a free window at those times
+    let mut free: BTreeMap<(NaiveTime, NaiveTime), Vec<NaiveDate>> = BTreeMap::new();
+    for window in windows {
+        let mut cursor = window.start;
+        let mut gaps = Vec::new();
+        for interval in busy
+            .iter()
+            .filter(|interval| interval.end > window.start && interval.start < window.end)
+        {
+            if interval.start > cursor {
+                gaps.push((cursor, interval.start));
+            }
+            cursor = cursor.max(interval.end);
+        }
+        if cursor < window.end {
+            gaps.push((cursor, window.end));
+        }
+        for (start, end) in gaps {
+            if end - start < Duration::minutes(MIN_FREE_MINUTES) {
+                continue;
+            }
+            let start = start.with_timezone(&tz).naive_local();
+            let end = end.with_timezone(&tz).naive_local();
+            if start.time() != end.time() {
+                free.entry((start.time(), end.time()))
+                    .or_default()
+                    .push(start.date());
+            }
+        }
+    }
+
+    let mut blocks: Vec<AvailableBlock> = free
+        .into_iter()
+        .filter_map(|((start_time, end_time), dates)| {
+            let rule = format!(
+                "DTSTART:{}\nRDATE:{}",
+                utc_date(dates[0]),
+                date_list(&dates)
+            );
+            Some(AvailableBlock {
+                start_time,
+                end_time,
+                need_warning: false,
+                repeats: MyRRuleSet::try_from(rule).ok()?,
+                player_id,
+                timezone: tz,
+            })
+        })
+        .collect();
+    blocks.sort_by_key(|block| (block.repeats.get_dt_start().date_naive(), block.start_time));
+    (blocks, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn events(body: &str) -> Vec<RawEvent> {
+        let text = format!("BEGIN:VCALENDAR\nVERSION:2.0\n{}\nEND:VCALENDAR\n", body);
+        ical::parse_events(&text).unwrap()
+    }
+
+    fn utc(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, day, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn until_is_rewritten_to_utc() {
+        let berlin = chrono_tz::Europe::Berlin;
+        assert_eq!(
+            utc_until("FREQ=WEEKLY;UNTIL=20261026T180000;BYDAY=MO", berlin).unwrap(),
+            "FREQ=WEEKLY;UNTIL=20261026T170000Z;BYDAY=MO"
+        );
+        // A date runs to the end of that day
+        assert_eq!(
+            utc_until("FREQ=DAILY;UNTIL=20261026", berlin).unwrap(),
+            "FREQ=DAILY;UNTIL=20261026T225959Z"
+        );
+        assert_eq!(utc_until("FREQ=DAILY;COUNT=3", berlin).unwrap(), "FREQ=DAILY;COUNT=3");
+        assert!(utc_until("FREQ=DAILY;UNTIL=soon", berlin).is_err());
+    }
+
+    #[test]
+    fn duration_defaults() {
+        let parsed = events(
+            "BEGIN:VEVENT\nUID:a\nDTSTART:20261019T180000Z\nDURATION:PT2H\nEND:VEVENT\n\
+             BEGIN:VEVENT\nUID:b\nDTSTART;VALUE=DATE:20261019\nEND:VEVENT\n\
+             BEGIN:VEVENT\nUID:c\nDTSTART:20261019T180000Z\nEND:VEVENT",
+        );
+        let durations: Vec<Option<Duration>> = parsed
+            .iter()
+            .map(|raw| event_duration(&raw.parse().unwrap(), chrono_tz::UTC))
+            .collect();
+        assert_eq!(
+            durations,
+            vec![
+                Some(Duration::hours(2)),
+                Some(Duration::days(1)),
+                Some(Duration::zero())
+            ]
+        );
+    }
+
+    #[test]
+    fn replaced_instances_leave_the_series() {
+        let parsed = events(
+            "BEGIN:VEVENT\nUID:weekly\nDTSTART:20261019T180000Z\nDTEND:20261019T200000Z\n\
+             RRULE:FREQ=DAILY;COUNT=3\nEND:VEVENT\n\
+             BEGIN:VEVENT\nUID:weekly\nRECURRENCE-ID:20261020T180000Z\n\
+             DTSTART:20261020T190000Z\nDTEND:20261020T210000Z\nEND:VEVENT",
+        );
+        let (parsed, skipped) = parse_events(&parsed);
+        assert!(skipped.is_empty());
+        let starts = event_starts(&parsed[0].1, chrono_tz::UTC, utc(18, 0), utc(31, 0)).unwrap();
+        assert_eq!(starts, vec![utc(19, 18), utc(21, 18)]);
+    }
+
+    #[test]
+    fn busy_time_is_cut_out_of_waking_hours() {
+        let parsed = events(
+            "BEGIN:VEVENT\nUID:work\nDTSTART:20261019T090000Z\nDTEND:20261019T170000Z\n\
+             RRULE:FREQ=DAILY;COUNT=2\nEND:VEVENT\n\
+             BEGIN:VEVENT\nUID:free\nTRANSP:TRANSPARENT\nDTSTART:20261019T120000Z\n\
+             DTEND:20261019T130000Z\nEND:VEVENT",
+        );
+        let waking = WakingHours {
+            start: NaiveTime::from_hms_opt(8, 45, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+        };
+        let (blocks, skipped) =
+            blocks_from_busy_time(&parsed, 1, chrono_tz::UTC, waking, utc(19, 0), utc(21, 0));
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].uid.as_deref(), Some("free"));
+
+        // 08:45-09:00 is under the minimum, both days share the evening block
+        let times: Vec<(String, String)> = blocks
+            .iter()
+            .map(|block| (block.start_time.to_string(), block.end_time.to_string()))
+            .collect();
+        assert_eq!(times, vec![("17:00:00".to_string(), "22:00:00".to_string())]);
+        assert_eq!(blocks[0].repeats.get_dt_start(), &utc(19, 0).with_timezone(&rrule::Tz::UTC));
+    }
+}
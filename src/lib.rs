@@ -0,0 +1,16 @@
+pub mod api;
+pub mod auth;
+pub mod availability;
+pub mod config;
+pub mod data;
+pub mod error;
+pub mod extract;
+pub mod ical;
+pub mod import;
+pub mod memory;
+pub mod metrics;
+pub mod migrations;
+pub mod model;
+pub mod policy;
+pub mod scheduling;
+pub mod telemetry;
@@ -1,39 +1,236 @@
-use api::DynAvailStore;
-use axum::{
-    http::StatusCode, routing::get_service, Router
-};
+use std::fmt::Display;
+use std::future::IntoFuture;
+use std::time::Duration;
+
+use axum::Router;
+use clap::{Parser, Subcommand};
 use tower_http::services:: { ServeDir, ServeFile};
-use data::PostgresAvailablityStore;
-use sqlx::postgres::PgPoolOptions;
-mod model;
-mod data;
-mod api;
-mod error;
+use sqlx::PgPool;
+use tokio::time::Instant;
+use team_availablity_coordinator::api::{self, DynAvailStore};
+use team_availablity_coordinator::config::{Config, ConfigArgs, DatabaseConfig, StoreKind};
+use team_availablity_coordinator::data::{AvailablityStore, PostgresAvailablityStore};
+use team_availablity_coordinator::error::Error;
+use team_availablity_coordinator::memory::InMemoryAvailablityStore;
+use team_availablity_coordinator::metrics;
+use team_availablity_coordinator::migrations;
+use team_availablity_coordinator::telemetry;
 
-#[tokio::main]
-async fn main() {
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Cli {
+    #[command(flatten)]
+    config: ConfigArgs,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Manage the database schema
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    /// Grant or revoke access to the /api/admin routes
+    Admin {
+        #[command(subcommand)]
+        action: AdminAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum MigrateAction {
+    /// List migrations and whether they have been applied
+    Status,
+    /// Apply all pending migrations
+    Up,
+    /// Revert the most recently applied migration
+    Down,
+}
+
+#[derive(Subcommand, Debug)]
+enum AdminAction {
+    /// Make the user with this name an admin
+    Grant { name: String },
+    /// Take the admin role away from the user with this name
+    Revoke { name: String },
+}
 
-    let db_url = std::env::var("DATABASE_URL").expect("Database url not found");
-    println!("{}",db_url);
+// Startup problems are reported without a backtrace, they are usually a wrong setting
+fn exit_with(err: impl Display) -> ! {
+    eprintln!("error: {}", err);
+    std::process::exit(1)
+}
 
-    let pool = PgPoolOptions::new()
-        .max_connections(5)
-        //TODO: Parse at runtime
-        .connect(db_url.as_str())
+async fn connect(config: &DatabaseConfig) -> PgPool {
+    let url = config.url().unwrap_or_else(|err| exit_with(err));
+    config
+        .pool_options()
+        .connect(url)
         .await
-        .expect("can connect to db");
+        .unwrap_or_else(|err| exit_with(format!("can't connect to the database: {}", err)))
+}
 
+// Resolves on the first SIGINT (Ctrl-C) or SIGTERM (docker stop)
+async fn shutdown_signal() {
+    let interrupt = async {
+        tokio::signal::ctrl_c().await.expect("can listen for SIGINT");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("can listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
+    }
+}
 
-    //TODO: Check this type with vid
-    let store = std::sync::Arc::new(PostgresAvailablityStore::new(pool)) as DynAvailStore;
-    let static_file_serve = get_service(ServeDir::new(env!("STATIC_DIR")).fallback(ServeFile::new(env!("STATIC_FILE")))).handle_error(|_| async move {
-        (StatusCode::INTERNAL_SERVER_ERROR, "internal server error")
-    });
+// Stops accepting connections on the signal and waits for in-flight requests until `timeout` has
+// passed, then returns that deadline. Connection tasks still running at that point aren't waited
+// for, they are cancelled when the runtime shuts down and their open transactions roll back
+async fn serve(listener: tokio::net::TcpListener, app: Router, timeout: Duration) -> Instant {
+    let (draining, mut drain_started) = tokio::sync::oneshot::channel();
+    let server = axum::serve(listener, app)
+        .with_graceful_shutdown(async move {
+            shutdown_signal().await;
+            tracing::info!("shutting down, waiting up to {}s for requests to finish", timeout.as_secs());
+            let _ = draining.send(Instant::now() + timeout);
+        })
+        .into_future();
+    tokio::pin!(server);
+    let deadline = tokio::select! {
+        result = &mut server => {
+            result.unwrap_or_else(|err| exit_with(format!("server failed: {}", err)));
+            return drain_started.try_recv().unwrap_or_else(|_| Instant::now());
+        }
+        Ok(deadline) = &mut drain_started => deadline,
+    };
+    tokio::select! {
+        result = server => result.unwrap_or_else(|err| exit_with(format!("server failed: {}", err))),
+        _ = tokio::time::sleep_until(deadline) => {
+            tracing::warn!("requests still running after {}s were abandoned", timeout.as_secs())
+        }
+    }
+    deadline
+}
 
-    let app = Router::new()
-        .nest("/api", api::api_routes(store))
-        .fallback(static_file_serve);
+async fn migrate(config: &Config, action: MigrateAction) {
+    let pool = connect(&config.database).await;
+    match action {
+        MigrateAction::Status => {
+            let migrations = migrations::status(&pool)
+                .await
+                .unwrap_or_else(|err| exit_with(format!("can't read migrations: {}", err)));
+            for migration in migrations {
+                let state = if migration.applied { "applied" } else { "pending" };
+                println!("{} {} ({})", migration.version, migration.description, state);
+            }
+        }
+        MigrateAction::Up => {
+            apply_migrations(&pool).await;
+            println!("Database is up to date");
+        }
+        MigrateAction::Down => {
+            let reverted = migrations::down(&pool)
+                .await
+                .unwrap_or_else(|err| exit_with(format!("can't revert migration: {}", err)));
+            match reverted {
+                Some(version) => println!("Reverted {}", version),
+                None => println!("No migrations to revert"),
+            }
+        }
+    }
+}
+
+async fn apply_migrations(pool: &PgPool) {
+    migrations::up(pool)
+        .await
+        .unwrap_or_else(|err| exit_with(format!("can't apply migrations: {}", err)));
+}
+
+async fn admin(config: &Config, action: AdminAction) {
+    let store = PostgresAvailablityStore::new(connect(&config.database).await);
+    let (name, admin) = match &action {
+        AdminAction::Grant { name } => (name, true),
+        AdminAction::Revoke { name } => (name, false),
+    };
+    let user = store
+        .get_user_by_name(name.clone())
+        .await
+        .unwrap_or_else(|err| exit_with(err))
+        .unwrap_or_else(|| exit_with(format!("no user is named {}", name)));
+    store
+        .set_admin(user.id, admin)
+        .await
+        .unwrap_or_else(|err| exit_with(err));
+    if admin {
+        println!("{} is an admin", name);
+    } else {
+        println!("{} is no longer an admin", name);
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let config = Config::load(&cli.config).unwrap_or_else(|err| exit_with(err));
+    telemetry::init(&config.log).unwrap_or_else(|err| exit_with(err));
+
+    match cli.command {
+        Some(Command::Migrate { action }) => return migrate(&config, action).await,
+        Some(Command::Admin { action }) => return admin(&config, action).await,
+        None => {}
+    }
+
+    // The memory store runs without a database, nothing is persisted
+    let (store, pool) = match config.store {
+        StoreKind::Memory => (std::sync::Arc::new(InMemoryAvailablityStore::new()) as DynAvailStore, None),
+        StoreKind::Postgres => {
+            let pool = connect(&config.database).await;
+            if !config.database.skip_migrations {
+                apply_migrations(&pool).await;
+            }
+
+            //TODO: Check this type with vid
+            (std::sync::Arc::new(PostgresAvailablityStore::new(pool.clone())) as DynAvailStore, Some(pool))
+        }
+    };
+    let mut app = Router::new().nest("/api", api::api_routes(store.clone()));
+    // Without a static directory only the API is served
+    if let Some(dir) = &config.static_files.dir {
+        app = match &config.static_files.fallback {
+            Some(fallback) => app.fallback_service(ServeDir::new(dir).fallback(ServeFile::new(fallback))),
+            None => app.fallback_service(ServeDir::new(dir)),
+        };
+    } else {
+        // The default fallback isn't layered, unknown paths would get no request id
+        app = app.fallback(|| async { Error::NotFound("route") });
+    }
+    if let Some(cors) = config.cors.layer() {
+        app = app.layer(cors);
+    }
+    // Probes are merged after the tracing layers so they stay out of the request logs and metrics
+    let app = metrics::track_requests(telemetry::trace_requests(app)).merge(api::health_routes(store));
+
+    let listener = tokio::net::TcpListener::bind(config.bind)
+        .await
+        .unwrap_or_else(|err| exit_with(format!("can't listen on {}: {}", config.bind, err)));
+    tracing::info!("listening on {}", config.bind);
+    let deadline = serve(listener, app, config.shutdown_timeout()).await;
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    // Abandoned requests still hold their connections, closing the pool would wait for them
+    if let Some(pool) = pool {
+        if tokio::time::timeout_at(deadline, pool.close()).await.is_err() {
+            tracing::warn!("database pool still busy at the shutdown deadline, exiting anyway");
+        }
+    }
+    tracing::info!("stopped");
 }
@@ -0,0 +1,1197 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::RwLock;
+
+use axum::async_trait;
+use sqlx::error::{DatabaseError, ErrorKind};
+
+use crate::data::AvailablityStore;
+use crate::model::*;
+
+// Mirrors the Postgres error a constraint from the migrations would raise, so callers can't tell
+// the stores apart
+#[derive(Debug)]
+struct ConstraintViolation {
+    kind: ErrorKind,
+    message: String,
+}
+
+impl std::fmt::Display for ConstraintViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ConstraintViolation {}
+
+impl DatabaseError for ConstraintViolation {
+    fn message(&self) -> &str {
+        &self.message
+    }
+
+    fn code(&self) -> Option<Cow<'_, str>> {
+        match self.kind {
+            ErrorKind::UniqueViolation => Some(Cow::Borrowed("23505")),
+            ErrorKind::ForeignKeyViolation => Some(Cow::Borrowed("23503")),
+            _ => None,
+        }
+    }
+
+    fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+
+    fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+
+    fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+        self
+    }
+
+    fn kind(&self) -> ErrorKind {
+        match self.kind {
+            ErrorKind::UniqueViolation => ErrorKind::UniqueViolation,
+            ErrorKind::ForeignKeyViolation => ErrorKind::ForeignKeyViolation,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+fn unique_violation(constraint: &str) -> sqlx::error::Error {
+    sqlx::error::Error::Database(Box::new(ConstraintViolation {
+        kind: ErrorKind::UniqueViolation,
+        message: format!(
+            "duplicate key value violates unique constraint \"{}\"",
+            constraint
+        ),
+    }))
+}
+
+fn foreign_key_violation(table: &str, constraint: &str) -> sqlx::error::Error {
+    sqlx::error::Error::Database(Box::new(ConstraintViolation {
+        kind: ErrorKind::ForeignKeyViolation,
+        message: format!(
+            "insert or update on table \"{}\" violates foreign key constraint \"{}\"",
+            table, constraint
+        ),
+    }))
+}
+
+fn still_referenced(table: &str, constraint: &str) -> sqlx::error::Error {
+    sqlx::error::Error::Database(Box::new(ConstraintViolation {
+        kind: ErrorKind::ForeignKeyViolation,
+        message: format!(
+            "update or delete on table \"{}\" violates foreign key constraint \"{}\"",
+            table, constraint
+        ),
+    }))
+}
+
+// Hands out ids the way a SERIAL column does, ids are never reused
+#[derive(Default)]
+struct Sequence(i32);
+
+impl Sequence {
+    fn next(&mut self) -> i32 {
+        self.0 += 1;
+        self.0
+    }
+}
+
+#[derive(Default)]
+struct Tables {
+    users: BTreeMap<i32, IdentifiableUser>,
+    users_seq: Sequence,
+    // users.password_hash, absent for users created without a password
+    password_hashes: BTreeMap<i32, String>,
+    // Keyed by token_hash
+    sessions: BTreeMap<String, Session>,
+    // The user ids in admins
+    admins: BTreeSet<i32>,
+    teams: BTreeMap<i32, IdentifiableTeam>,
+    teams_seq: Sequence,
+    // The invite_* columns of teams, keyed by team id
+    invites: BTreeMap<i32, TeamInvite>,
+    // The feed_token column of teams, keyed by team id
+    feeds: BTreeMap<i32, String>,
+    // The feed_token column of players, keyed by player id
+    player_feeds: BTreeMap<i32, String>,
+    join_requests: BTreeMap<i32, JoinRequest>,
+    join_requests_seq: Sequence,
+    team_events: BTreeMap<i32, IdentifiableTeamEvent>,
+    team_events_seq: Sequence,
+    // Keyed by (event_id, player_id), the primary key of event_rsvps
+    rsvps: BTreeMap<(i32, i32), Rsvp>,
+    players: BTreeMap<i32, IdentifiablePlayer>,
+    players_seq: Sequence,
+    // Keyed by (player_id, team_id), the primary key of players_to_teams
+    players_to_teams: BTreeMap<(i32, i32), TeamRole>,
+    available_blocks: BTreeMap<i32, IdentifiableAvailableBlock>,
+    available_blocks_seq: Sequence,
+}
+
+impl Tables {
+    fn check_user_name(&self, name: &str, except_id: Option<i32>) -> Result<(), sqlx::error::Error> {
+        if self
+            .users
+            .values()
+            .any(|user| user.name == name && Some(user.id) != except_id)
+        {
+            return Err(unique_violation("users_name_key"));
+        }
+        Ok(())
+    }
+
+    fn check_team_name(&self, name: &str, except_id: Option<i32>) -> Result<(), sqlx::error::Error> {
+        if self
+            .teams
+            .values()
+            .any(|team| team.name == name && Some(team.id) != except_id)
+        {
+            return Err(unique_violation("teams_name_key"));
+        }
+        Ok(())
+    }
+
+    fn check_user_exists(&self, user_id: i32) -> Result<(), sqlx::error::Error> {
+        if !self.users.contains_key(&user_id) {
+            return Err(foreign_key_violation("players", "players_user_id_fkey"));
+        }
+        Ok(())
+    }
+
+    fn insert_membership(
+        &mut self,
+        membership: PlayerToTeam,
+    ) -> Result<PlayerToTeam, sqlx::error::Error> {
+        self.check_player_exists("players_to_teams", membership.player_id)?;
+        if !self.teams.contains_key(&membership.team_id) {
+            return Err(foreign_key_violation(
+                "players_to_teams",
+                "players_to_teams_team_id_fkey",
+            ));
+        }
+        let key = (membership.player_id, membership.team_id);
+        if self.players_to_teams.contains_key(&key) {
+            return Err(unique_violation("players_to_teams_pkey"));
+        }
+        self.players_to_teams.insert(key, membership.role);
+        Ok(membership)
+    }
+
+    fn check_player_exists(&self, table: &str, player_id: i32) -> Result<(), sqlx::error::Error> {
+        if !self.players.contains_key(&player_id) {
+            return Err(foreign_key_violation(
+                table,
+                &format!("{}_player_id_fkey", table),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn to_raw(block: &IdentifiableAvailableBlock) -> RawAvailableBlock {
+    RawAvailableBlock {
+        id: block.id,
+        start_time: Some(block.inner_block.start_time),
+        end_time: Some(block.inner_block.end_time),
+        need_warning: block.inner_block.need_warning,
+        repeats: Some(block.inner_block.repeats.to_string()),
+        player_id: block.inner_block.player_id,
+        timezone: block.inner_block.timezone.name().to_string(),
+    }
+}
+
+/// Keeps everything in memory, enforcing the same keys and constraints as the migrations.
+/// Meant for tests and for running the API without a database.
+#[derive(Default)]
+pub struct InMemoryAvailablityStore {
+    tables: RwLock<Tables>,
+}
+
+impl InMemoryAvailablityStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl AvailablityStore for InMemoryAvailablityStore {
+    //Health
+    async fn ping(&self) -> Result<(), sqlx::error::Error> {
+        Ok(())
+    }
+
+    fn pool_status(&self) -> Option<PoolStatus> {
+        None
+    }
+
+    async fn applied_migration(&self) -> Result<Option<i64>, sqlx::error::Error> {
+        Ok(None)
+    }
+
+    //Users
+    async fn get_user_by_id(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<IdentifiableUser>, sqlx::error::Error> {
+        Ok(self.tables.read().unwrap().users.get(&user_id).cloned())
+    }
+
+    async fn get_user_by_name(
+        &self,
+        user_name: String,
+    ) -> Result<Option<IdentifiableUser>, sqlx::error::Error> {
+        Ok(self
+            .tables
+            .read()
+            .unwrap()
+            .users
+            .values()
+            .find(|user| user.name == user_name)
+            .cloned())
+    }
+
+    async fn add_user(&self, user: User) -> Result<IdentifiableUser, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        tables.check_user_name(&user.name, None)?;
+        let user = IdentifiableUser {
+            id: tables.users_seq.next(),
+            name: user.name,
+        };
+        tables.users.insert(user.id, user.clone());
+        Ok(user)
+    }
+
+    async fn update_user(&self, user: IdentifiableUser) -> Result<IdentifiableUser, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        if !tables.users.contains_key(&user.id) {
+            return Err(sqlx::error::Error::RowNotFound);
+        }
+        tables.check_user_name(&user.name, Some(user.id))?;
+        tables.users.insert(user.id, user.clone());
+        Ok(user)
+    }
+
+    async fn delete_user(&self, user_id: i32) -> Result<(), sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        if tables.players.values().any(|player| player.user_id == user_id) {
+            return Err(still_referenced("users", "players_user_id_fkey"));
+        }
+        tables.users.remove(&user_id);
+        tables.password_hashes.remove(&user_id);
+        // ON DELETE CASCADE
+        tables.sessions.retain(|_, session| session.user_id != user_id);
+        tables.admins.remove(&user_id);
+        // ON DELETE SET NULL
+        for request in tables.join_requests.values_mut() {
+            if request.resolved_by == Some(user_id) {
+                request.resolved_by = None;
+            }
+        }
+        for event in tables.team_events.values_mut() {
+            if event.created_by == Some(user_id) {
+                event.created_by = None;
+            }
+        }
+        Ok(())
+    }
+
+    //Auth
+    async fn add_user_with_password(
+        &self,
+        user: User,
+        password_hash: String,
+    ) -> Result<IdentifiableUser, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        tables.check_user_name(&user.name, None)?;
+        let user = IdentifiableUser {
+            id: tables.users_seq.next(),
+            name: user.name,
+        };
+        tables.users.insert(user.id, user.clone());
+        tables.password_hashes.insert(user.id, password_hash);
+        Ok(user)
+    }
+
+    async fn get_user_credentials_by_name(
+        &self,
+        user_name: String,
+    ) -> Result<Option<UserCredentials>, sqlx::error::Error> {
+        let tables = self.tables.read().unwrap();
+        Ok(tables
+            .users
+            .values()
+            .find(|user| user.name == user_name)
+            .map(|user| UserCredentials {
+                id: user.id,
+                name: user.name.clone(),
+                password_hash: tables.password_hashes.get(&user.id).cloned(),
+            }))
+    }
+
+    async fn add_session(&self, session: Session) -> Result<Session, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        if !tables.users.contains_key(&session.user_id) {
+            return Err(foreign_key_violation("sessions", "sessions_user_id_fkey"));
+        }
+        if tables.sessions.contains_key(&session.token_hash) {
+            return Err(unique_violation("sessions_pkey"));
+        }
+        tables
+            .sessions
+            .insert(session.token_hash.clone(), session.clone());
+        Ok(session)
+    }
+
+    async fn get_user_by_session(
+        &self,
+        token_hash: String,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<IdentifiableUser>, sqlx::error::Error> {
+        let tables = self.tables.read().unwrap();
+        Ok(tables
+            .sessions
+            .get(&token_hash)
+            .filter(|session| session.expires_at > now)
+            .and_then(|session| tables.users.get(&session.user_id))
+            .cloned())
+    }
+
+    async fn delete_session(&self, token_hash: String) -> Result<(), sqlx::error::Error> {
+        self.tables.write().unwrap().sessions.remove(&token_hash);
+        Ok(())
+    }
+
+    //Admins
+    async fn is_admin(&self, user_id: i32) -> Result<bool, sqlx::error::Error> {
+        Ok(self.tables.read().unwrap().admins.contains(&user_id))
+    }
+
+    async fn set_admin(&self, user_id: i32, admin: bool) -> Result<(), sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        if !admin {
+            tables.admins.remove(&user_id);
+            return Ok(());
+        }
+        if !tables.users.contains_key(&user_id) {
+            return Err(foreign_key_violation("admins", "admins_user_id_fkey"));
+        }
+        tables.admins.insert(user_id);
+        Ok(())
+    }
+
+    //Teams
+    async fn get_team_by_id(
+        &self,
+        team_id: i32,
+    ) -> Result<Option<IdentifiableTeam>, sqlx::error::Error> {
+        Ok(self.tables.read().unwrap().teams.get(&team_id).cloned())
+    }
+
+    async fn get_team_by_name(
+        &self,
+        team_name: String,
+    ) -> Result<Option<IdentifiableTeam>, sqlx::error::Error> {
+        Ok(self
+            .tables
+            .read()
+            .unwrap()
+            .teams
+            .values()
+            .find(|team| team.name == team_name)
+            .cloned())
+    }
+
+    async fn add_team(&self, team: Team) -> Result<IdentifiableTeam, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        tables.check_team_name(&team.name, None)?;
+        let team = IdentifiableTeam {
+            id: tables.teams_seq.next(),
+            name: team.name,
+        };
+        tables.teams.insert(team.id, team.clone());
+        Ok(team)
+    }
+
+    async fn add_team_with_owner(
+        &self,
+        team: Team,
+        player_id: i32,
+    ) -> Result<IdentifiableTeam, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        tables.check_team_name(&team.name, None)?;
+        // Checked up front so a failed insert doesn't leave a team behind, like the transaction
+        tables.check_player_exists("players_to_teams", player_id)?;
+        let team = IdentifiableTeam {
+            id: tables.teams_seq.next(),
+            name: team.name,
+        };
+        tables.teams.insert(team.id, team.clone());
+        tables.insert_membership(PlayerToTeam {
+            player_id,
+            team_id: team.id,
+            role: TeamRole::Owner,
+        })?;
+        Ok(team)
+    }
+
+    async fn update_team(
+        &self,
+        team: IdentifiableTeam,
+    ) -> Result<IdentifiableTeam, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        if !tables.teams.contains_key(&team.id) {
+            return Err(sqlx::error::Error::RowNotFound);
+        }
+        tables.check_team_name(&team.name, Some(team.id))?;
+        tables.teams.insert(team.id, team.clone());
+        Ok(team)
+    }
+
+    async fn delete_team(&self, team_id: i32) -> Result<(), sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        // ON DELETE CASCADE
+        tables
+            .players_to_teams
+            .retain(|(_, member_team_id), _| *member_team_id != team_id);
+        tables.invites.remove(&team_id);
+        tables.feeds.remove(&team_id);
+        tables
+            .join_requests
+            .retain(|_, request| request.team_id != team_id);
+        let event_ids: Vec<i32> = tables
+            .team_events
+            .values()
+            .filter(|event| event.team_id == team_id)
+            .map(|event| event.id)
+            .collect();
+        tables
+            .rsvps
+            .retain(|(event_id, _), _| !event_ids.contains(event_id));
+        tables.team_events.retain(|_, event| event.team_id != team_id);
+        tables.teams.remove(&team_id);
+        Ok(())
+    }
+
+    //Invites
+    async fn get_team_invite(
+        &self,
+        team_id: i32,
+    ) -> Result<Option<TeamInvite>, sqlx::error::Error> {
+        Ok(self.tables.read().unwrap().invites.get(&team_id).cloned())
+    }
+
+    async fn get_team_invite_by_code(
+        &self,
+        code: String,
+    ) -> Result<Option<TeamInvite>, sqlx::error::Error> {
+        Ok(self
+            .tables
+            .read()
+            .unwrap()
+            .invites
+            .values()
+            .find(|invite| invite.code == code)
+            .cloned())
+    }
+
+    async fn set_team_invite(&self, invite: TeamInvite) -> Result<TeamInvite, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        if !tables.teams.contains_key(&invite.team_id) {
+            return Err(sqlx::error::Error::RowNotFound);
+        }
+        if tables
+            .invites
+            .values()
+            .any(|other| other.code == invite.code && other.team_id != invite.team_id)
+        {
+            return Err(unique_violation("teams_invite_code_key"));
+        }
+        tables.invites.insert(invite.team_id, invite.clone());
+        Ok(invite)
+    }
+
+    async fn delete_team_invite(&self, team_id: i32) -> Result<(), sqlx::error::Error> {
+        self.tables.write().unwrap().invites.remove(&team_id);
+        Ok(())
+    }
+
+    async fn redeem_team_invite(
+        &self,
+        code: String,
+        player_id: i32,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<PlayerToTeam>, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        let Some(team_id) = tables
+            .invites
+            .values()
+            .find(|invite| invite.code == code && !invite.is_expired(now) && !invite.is_used_up())
+            .map(|invite| invite.team_id)
+        else {
+            return Ok(None);
+        };
+        // Only counted once the membership exists, like the rolled back transaction
+        let membership = tables.insert_membership(PlayerToTeam {
+            player_id,
+            team_id,
+            role: TeamRole::Member,
+        })?;
+        if let Some(invite) = tables.invites.get_mut(&team_id) {
+            invite.uses += 1;
+        }
+        Ok(Some(membership))
+    }
+
+    //Feeds
+    async fn get_team_feed(&self, team_id: i32) -> Result<Option<TeamFeed>, sqlx::error::Error> {
+        Ok(self
+            .tables
+            .read()
+            .unwrap()
+            .feeds
+            .get(&team_id)
+            .map(|token| TeamFeed {
+                team_id,
+                token: token.clone(),
+            }))
+    }
+
+    async fn get_team_feed_by_token(
+        &self,
+        token: String,
+    ) -> Result<Option<TeamFeed>, sqlx::error::Error> {
+        Ok(self
+            .tables
+            .read()
+            .unwrap()
+            .feeds
+            .iter()
+            .find(|(_, other)| **other == token)
+            .map(|(team_id, token)| TeamFeed {
+                team_id: *team_id,
+                token: token.clone(),
+            }))
+    }
+
+    async fn set_team_feed(&self, feed: TeamFeed) -> Result<TeamFeed, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        if !tables.teams.contains_key(&feed.team_id) {
+            return Err(sqlx::error::Error::RowNotFound);
+        }
+        if tables
+            .feeds
+            .iter()
+            .any(|(team_id, token)| *token == feed.token && *team_id != feed.team_id)
+        {
+            return Err(unique_violation("teams_feed_token_key"));
+        }
+        tables.feeds.insert(feed.team_id, feed.token.clone());
+        Ok(feed)
+    }
+
+    async fn delete_team_feed(&self, team_id: i32) -> Result<(), sqlx::error::Error> {
+        self.tables.write().unwrap().feeds.remove(&team_id);
+        Ok(())
+    }
+
+    async fn get_player_feed(
+        &self,
+        player_id: i32,
+    ) -> Result<Option<PlayerFeed>, sqlx::error::Error> {
+        Ok(self
+            .tables
+            .read()
+            .unwrap()
+            .player_feeds
+            .get(&player_id)
+            .map(|token| PlayerFeed {
+                player_id,
+                token: token.clone(),
+            }))
+    }
+
+    async fn get_player_feed_by_token(
+        &self,
+        token: String,
+    ) -> Result<Option<PlayerFeed>, sqlx::error::Error> {
+        Ok(self
+            .tables
+            .read()
+            .unwrap()
+            .player_feeds
+            .iter()
+            .find(|(_, other)| **other == token)
+            .map(|(player_id, token)| PlayerFeed {
+                player_id: *player_id,
+                token: token.clone(),
+            }))
+    }
+
+    async fn set_player_feed(&self, feed: PlayerFeed) -> Result<PlayerFeed, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        if !tables.players.contains_key(&feed.player_id) {
+            return Err(sqlx::error::Error::RowNotFound);
+        }
+        if tables
+            .player_feeds
+            .iter()
+            .any(|(player_id, token)| *token == feed.token && *player_id != feed.player_id)
+        {
+            return Err(unique_violation("players_feed_token_key"));
+        }
+        tables.player_feeds.insert(feed.player_id, feed.token.clone());
+        Ok(feed)
+    }
+
+    async fn delete_player_feed(&self, player_id: i32) -> Result<(), sqlx::error::Error> {
+        self.tables.write().unwrap().player_feeds.remove(&player_id);
+        Ok(())
+    }
+
+    //Events
+    async fn add_team_event(
+        &self,
+        team_id: i32,
+        event: TeamEvent,
+        created_by: i32,
+    ) -> Result<IdentifiableTeamEvent, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        if !tables.teams.contains_key(&team_id) {
+            return Err(foreign_key_violation("team_events", "team_events_team_id_fkey"));
+        }
+        let event = IdentifiableTeamEvent {
+            id: tables.team_events_seq.next(),
+            team_id,
+            inner_event: event,
+            created_by: Some(created_by),
+            created_at: chrono::Utc::now(),
+        };
+        tables.team_events.insert(event.id, event.clone());
+        Ok(event)
+    }
+
+    async fn get_team_event_by_id(
+        &self,
+        event_id: i32,
+    ) -> Result<Option<IdentifiableTeamEvent>, sqlx::error::Error> {
+        Ok(self.tables.read().unwrap().team_events.get(&event_id).cloned())
+    }
+
+    async fn get_team_events_by_team_id(
+        &self,
+        team_id: i32,
+    ) -> Result<Vec<IdentifiableTeamEvent>, sqlx::error::Error> {
+        let mut events: Vec<IdentifiableTeamEvent> = self
+            .tables
+            .read()
+            .unwrap()
+            .team_events
+            .values()
+            .filter(|event| event.team_id == team_id)
+            .cloned()
+            .collect();
+        events.sort_by_key(|event| (event.inner_event.start, event.id));
+        Ok(events)
+    }
+
+    async fn update_team_event(
+        &self,
+        event: IdentifiableTeamEvent,
+    ) -> Result<IdentifiableTeamEvent, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        let Some(stored) = tables.team_events.get_mut(&event.id) else {
+            return Err(sqlx::error::Error::RowNotFound);
+        };
+        stored.inner_event = event.inner_event;
+        Ok(stored.clone())
+    }
+
+    async fn delete_team_event(&self, event_id: i32) -> Result<(), sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        // ON DELETE CASCADE
+        tables
+            .rsvps
+            .retain(|(rsvp_event_id, _), _| *rsvp_event_id != event_id);
+        tables.team_events.remove(&event_id);
+        Ok(())
+    }
+
+    //RSVPs
+    async fn set_rsvp(
+        &self,
+        event_id: i32,
+        player_id: i32,
+        response: RsvpResponse,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Rsvp, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        if !tables.team_events.contains_key(&event_id) {
+            return Err(foreign_key_violation("event_rsvps", "event_rsvps_event_id_fkey"));
+        }
+        tables.check_player_exists("event_rsvps", player_id)?;
+        let rsvp = Rsvp {
+            event_id,
+            player_id,
+            response,
+            updated_at: now,
+        };
+        tables.rsvps.insert((event_id, player_id), rsvp.clone());
+        Ok(rsvp)
+    }
+
+    async fn get_rsvps_by_event_id(&self, event_id: i32) -> Result<Vec<Rsvp>, sqlx::error::Error> {
+        Ok(self
+            .tables
+            .read()
+            .unwrap()
+            .rsvps
+            .values()
+            .filter(|rsvp| rsvp.event_id == event_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn delete_rsvp(&self, event_id: i32, player_id: i32) -> Result<(), sqlx::error::Error> {
+        self.tables
+            .write()
+            .unwrap()
+            .rsvps
+            .remove(&(event_id, player_id));
+        Ok(())
+    }
+
+    //Join requests
+    async fn add_join_request(
+        &self,
+        team_id: i32,
+        player_id: i32,
+        message: Option<String>,
+    ) -> Result<JoinRequest, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        if !tables.teams.contains_key(&team_id) {
+            return Err(foreign_key_violation(
+                "join_requests",
+                "join_requests_team_id_fkey",
+            ));
+        }
+        tables.check_player_exists("join_requests", player_id)?;
+        if tables.join_requests.values().any(|request| {
+            request.team_id == team_id
+                && request.player_id == player_id
+                && request.status == JoinRequestStatus::Pending
+        }) {
+            return Err(unique_violation("join_requests_pending_key"));
+        }
+        let request = JoinRequest {
+            id: tables.join_requests_seq.next(),
+            team_id,
+            player_id,
+            status: JoinRequestStatus::Pending,
+            message,
+            created_at: chrono::Utc::now(),
+            resolved_at: None,
+            resolved_by: None,
+        };
+        tables.join_requests.insert(request.id, request.clone());
+        Ok(request)
+    }
+
+    async fn get_join_request_by_id(
+        &self,
+        request_id: i32,
+    ) -> Result<Option<JoinRequest>, sqlx::error::Error> {
+        Ok(self
+            .tables
+            .read()
+            .unwrap()
+            .join_requests
+            .get(&request_id)
+            .cloned())
+    }
+
+    async fn get_join_requests_by_team_id(
+        &self,
+        team_id: i32,
+        status: Option<JoinRequestStatus>,
+    ) -> Result<Vec<JoinRequest>, sqlx::error::Error> {
+        let mut requests: Vec<JoinRequest> = self
+            .tables
+            .read()
+            .unwrap()
+            .join_requests
+            .values()
+            .filter(|request| {
+                request.team_id == team_id && status.is_none_or(|status| request.status == status)
+            })
+            .cloned()
+            .collect();
+        requests.sort_by_key(|request| (request.created_at, request.id));
+        Ok(requests)
+    }
+
+    async fn resolve_join_request(
+        &self,
+        request_id: i32,
+        status: JoinRequestStatus,
+        resolved_by: i32,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<JoinRequest>, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        let Some(request) = tables
+            .join_requests
+            .get(&request_id)
+            .filter(|request| request.status == JoinRequestStatus::Pending)
+            .cloned()
+        else {
+            return Ok(None);
+        };
+        let request = JoinRequest {
+            status,
+            resolved_at: Some(now),
+            resolved_by: Some(resolved_by),
+            ..request
+        };
+        // The membership goes in first so a failed insert leaves the request pending
+        if status == JoinRequestStatus::Approved {
+            tables.insert_membership(PlayerToTeam {
+                player_id: request.player_id,
+                team_id: request.team_id,
+                role: TeamRole::Member,
+            })?;
+        }
+        tables.join_requests.insert(request.id, request.clone());
+        Ok(Some(request))
+    }
+
+    //Players
+    async fn get_player_by_id(
+        &self,
+        player_id: i32,
+    ) -> Result<Option<IdentifiablePlayer>, sqlx::error::Error> {
+        Ok(self.tables.read().unwrap().players.get(&player_id).cloned())
+    }
+
+    async fn get_player_by_user_id(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<IdentifiablePlayer>, sqlx::error::Error> {
+        Ok(self
+            .tables
+            .read()
+            .unwrap()
+            .players
+            .values()
+            .find(|player| player.user_id == user_id)
+            .cloned())
+    }
+
+    async fn add_player(&self, player: Player) -> Result<IdentifiablePlayer, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        tables.check_user_exists(player.user_id)?;
+        let player = IdentifiablePlayer {
+            id: tables.players_seq.next(),
+            user_id: player.user_id,
+        };
+        tables.players.insert(player.id, player.clone());
+        Ok(player)
+    }
+
+    async fn update_player(
+        &self,
+        player: IdentifiablePlayer,
+    ) -> Result<IdentifiablePlayer, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        if !tables.players.contains_key(&player.id) {
+            return Err(sqlx::error::Error::RowNotFound);
+        }
+        tables.check_user_exists(player.user_id)?;
+        tables.players.insert(player.id, player.clone());
+        Ok(player)
+    }
+
+    async fn delete_player(&self, player_id: i32) -> Result<(), sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        if tables
+            .players_to_teams
+            .keys()
+            .any(|(member_player_id, _)| *member_player_id == player_id)
+        {
+            return Err(still_referenced("players", "players_to_teams_player_id_fkey"));
+        }
+        if tables
+            .available_blocks
+            .values()
+            .any(|block| block.inner_block.player_id == player_id)
+        {
+            return Err(still_referenced("players", "available_blocks_player_id_fkey"));
+        }
+        // ON DELETE CASCADE
+        tables
+            .join_requests
+            .retain(|_, request| request.player_id != player_id);
+        tables
+            .rsvps
+            .retain(|(_, rsvp_player_id), _| *rsvp_player_id != player_id);
+        tables.player_feeds.remove(&player_id);
+        tables.players.remove(&player_id);
+        Ok(())
+    }
+
+    //Rosters
+    async fn add_player_to_team(
+        &self,
+        membership: PlayerToTeam,
+    ) -> Result<PlayerToTeam, sqlx::error::Error> {
+        self.tables.write().unwrap().insert_membership(membership)
+    }
+
+    async fn remove_player_from_team(
+        &self,
+        membership: PlayerToTeam,
+    ) -> Result<(), sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        if tables
+            .players_to_teams
+            .remove(&(membership.player_id, membership.team_id))
+            .is_none()
+        {
+            return Err(sqlx::error::Error::RowNotFound);
+        }
+        Ok(())
+    }
+
+    async fn update_team_role(
+        &self,
+        membership: PlayerToTeam,
+    ) -> Result<PlayerToTeam, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        match tables
+            .players_to_teams
+            .get_mut(&(membership.player_id, membership.team_id))
+        {
+            Some(role) => {
+                *role = membership.role;
+                Ok(membership)
+            }
+            None => Err(sqlx::error::Error::RowNotFound),
+        }
+    }
+
+    async fn get_team_membership(
+        &self,
+        team_id: i32,
+        player_id: i32,
+    ) -> Result<Option<PlayerToTeam>, sqlx::error::Error> {
+        Ok(self
+            .tables
+            .read()
+            .unwrap()
+            .players_to_teams
+            .get(&(player_id, team_id))
+            .map(|role| PlayerToTeam {
+                player_id,
+                team_id,
+                role: *role,
+            }))
+    }
+
+    async fn get_memberships_by_player_id(
+        &self,
+        player_id: i32,
+    ) -> Result<Vec<PlayerToTeam>, sqlx::error::Error> {
+        Ok(self
+            .tables
+            .read()
+            .unwrap()
+            .players_to_teams
+            .iter()
+            .filter(|((member_player_id, _), _)| *member_player_id == player_id)
+            .map(|((player_id, team_id), role)| PlayerToTeam {
+                player_id: *player_id,
+                team_id: *team_id,
+                role: *role,
+            })
+            .collect())
+    }
+
+    async fn get_team_roster(
+        &self,
+        team_id: i32,
+    ) -> Result<Vec<RosterMember>, sqlx::error::Error> {
+        let tables = self.tables.read().unwrap();
+        let mut roster: Vec<RosterMember> = tables
+            .players_to_teams
+            .iter()
+            .filter(|((_, member_team_id), _)| *member_team_id == team_id)
+            .filter_map(|((player_id, _), role)| {
+                let player = tables.players.get(player_id)?;
+                let user = tables.users.get(&player.user_id)?;
+                Some(RosterMember {
+                    player_id: player.id,
+                    user_id: user.id,
+                    name: user.name.clone(),
+                    role: *role,
+                })
+            })
+            .collect();
+        roster.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(roster)
+    }
+
+    async fn get_teams_by_player_id(
+        &self,
+        player_id: i32,
+    ) -> Result<Vec<IdentifiableTeam>, sqlx::error::Error> {
+        let tables = self.tables.read().unwrap();
+        let mut teams: Vec<IdentifiableTeam> = tables
+            .players_to_teams
+            .keys()
+            .filter(|(member_player_id, _)| *member_player_id == player_id)
+            .filter_map(|(_, team_id)| tables.teams.get(team_id).cloned())
+            .collect();
+        teams.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(teams)
+    }
+
+    // Blocks
+    async fn get_available_block_by_id(
+        &self,
+        block_id: i32,
+    ) -> Result<Option<IdentifiableAvailableBlock>, sqlx::error::Error> {
+        Ok(self
+            .tables
+            .read()
+            .unwrap()
+            .available_blocks
+            .get(&block_id)
+            .cloned())
+    }
+
+    async fn get_available_blocks_by_player_id(
+        &self,
+        player_id: i32,
+    ) -> Result<Vec<IdentifiableAvailableBlock>, sqlx::error::Error> {
+        Ok(self
+            .tables
+            .read()
+            .unwrap()
+            .available_blocks
+            .values()
+            .filter(|block| block.inner_block.player_id == player_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_raw_available_blocks(&self) -> Result<Vec<RawAvailableBlock>, sqlx::error::Error> {
+        Ok(self
+            .tables
+            .read()
+            .unwrap()
+            .available_blocks
+            .values()
+            .map(to_raw)
+            .collect())
+    }
+
+    async fn get_raw_available_blocks_by_player_id(
+        &self,
+        player_id: i32,
+    ) -> Result<Vec<RawAvailableBlock>, sqlx::error::Error> {
+        Ok(self
+            .get_available_blocks_by_player_id(player_id)
+            .await?
+            .iter()
+            .map(to_raw)
+            .collect())
+    }
+
+    async fn get_raw_available_blocks_by_team_id(
+        &self,
+        team_id: i32,
+    ) -> Result<Vec<RawAvailableBlock>, sqlx::error::Error> {
+        let tables = self.tables.read().unwrap();
+        Ok(tables
+            .available_blocks
+            .values()
+            .filter(|block| {
+                tables
+                    .players_to_teams
+                    .contains_key(&(block.inner_block.player_id, team_id))
+            })
+            .map(to_raw)
+            .collect())
+    }
+
+    async fn add_available_block(
+        &self,
+        block: AvailableBlock,
+    ) -> Result<IdentifiableAvailableBlock, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        tables.check_player_exists("available_blocks", block.player_id)?;
+        let block = IdentifiableAvailableBlock {
+            id: tables.available_blocks_seq.next(),
+            inner_block: block,
+        };
+        tables.available_blocks.insert(block.id, block.clone());
+        Ok(block)
+    }
+
+    async fn add_available_blocks(
+        &self,
+        blocks: Vec<AvailableBlock>,
+    ) -> Result<Vec<IdentifiableAvailableBlock>, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        for block in &blocks {
+            tables.check_player_exists("available_blocks", block.player_id)?;
+        }
+        Ok(blocks
+            .into_iter()
+            .map(|block| {
+                let block = IdentifiableAvailableBlock {
+                    id: tables.available_blocks_seq.next(),
+                    inner_block: block,
+                };
+                tables.available_blocks.insert(block.id, block.clone());
+                block
+            })
+            .collect())
+    }
+
+    async fn update_available_block(
+        &self,
+        block: IdentifiableAvailableBlock,
+    ) -> Result<IdentifiableAvailableBlock, sqlx::error::Error> {
+        let mut tables = self.tables.write().unwrap();
+        if !tables.available_blocks.contains_key(&block.id) {
+            return Err(sqlx::error::Error::RowNotFound);
+        }
+        tables.check_player_exists("available_blocks", block.inner_block.player_id)?;
+        tables.available_blocks.insert(block.id, block.clone());
+        Ok(block)
+    }
+
+    async fn delete_available_block(&self, block_id: i32) -> Result<(), sqlx::error::Error> {
+        self.tables
+            .write()
+            .unwrap()
+            .available_blocks
+            .remove(&block_id);
+        Ok(())
+    }
+
+    async fn get_available_block_player_id(
+        &self,
+        block_id: i32,
+    ) -> Result<Option<i32>, sqlx::error::Error> {
+        Ok(self
+            .tables
+            .read()
+            .unwrap()
+            .available_blocks
+            .get(&block_id)
+            .map(|block| block.inner_block.player_id))
+    }
+}
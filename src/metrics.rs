@@ -0,0 +1,113 @@
+use std::sync::LazyLock;
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::{self, Next},
+    response::Response,
+    Router,
+};
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, register_int_gauge, Encoder, HistogramVec,
+    IntCounterVec, IntGauge, TextEncoder,
+};
+
+use crate::model::PoolStatus;
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+// Requests that matched no route share one label, raw paths would make a series per id
+const FALLBACK_ROUTE: &str = "fallback";
+
+static HTTP_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "http_requests_total",
+        "Requests answered, by route template and status",
+        &["method", "route", "status"]
+    )
+    .unwrap()
+});
+
+static HTTP_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "http_request_duration_seconds",
+        "Time taken to answer requests, by route template",
+        &["method", "route"]
+    )
+    .unwrap()
+});
+
+static DB_POOL_CONNECTIONS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("db_pool_connections", "Open connections in the database pool").unwrap()
+});
+
+static DB_POOL_IDLE: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("db_pool_idle_connections", "Open connections not in use").unwrap()
+});
+
+static DB_POOL_MAX: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("db_pool_max_connections", "Most connections the pool opens").unwrap()
+});
+
+static BLOCKS_CREATED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "available_blocks_created_total",
+        "Available blocks saved, by whether they were created directly or imported",
+        &["source"]
+    )
+    .unwrap()
+});
+
+static OVERLAP_COMPUTATIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "team_overlap_computations_total",
+        "Roster availability overlaps computed, by what they were computed for",
+        &["kind"]
+    )
+    .unwrap()
+});
+
+pub fn blocks_created(source: &str, count: usize) {
+    BLOCKS_CREATED
+        .with_label_values(&[source])
+        .inc_by(count as u64);
+}
+
+pub fn overlap_computed(kind: &str) {
+    OVERLAP_COMPUTATIONS.with_label_values(&[kind]).inc();
+}
+
+/// Counts and times every request to `router`'s routes, labelled by the route template
+pub fn track_requests(router: Router) -> Router {
+    router.layer(middleware::from_fn(track))
+}
+
+async fn track(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| FALLBACK_ROUTE.to_string());
+    let started = Instant::now();
+    let response = next.run(request).await;
+    HTTP_REQUESTS
+        .with_label_values(&[method.as_str(), &route, response.status().as_str()])
+        .inc();
+    HTTP_REQUEST_DURATION
+        .with_label_values(&[method.as_str(), &route])
+        .observe(started.elapsed().as_secs_f64());
+    response
+}
+
+/// Every metric in the Prometheus text format, the pool gauges are read when scraped
+pub fn render(pool: Option<PoolStatus>) -> Result<String, prometheus::Error> {
+    if let Some(pool) = pool {
+        DB_POOL_CONNECTIONS.set(pool.size.into());
+        DB_POOL_IDLE.set(pool.idle.into());
+        DB_POOL_MAX.set(pool.max.into());
+    }
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&prometheus::gather(), &mut buffer)?;
+    String::from_utf8(buffer).map_err(|err| prometheus::Error::Msg(err.to_string()))
+}
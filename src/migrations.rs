@@ -0,0 +1,67 @@
+use std::collections::HashSet;
+
+use sqlx::migrate::{Migrate, MigrateError, MigrationType, Migrator};
+use sqlx::PgPool;
+
+// Embedded into the binary at compile time from ./migrations
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+// The up migrations in order, reversible migrations also carry a down entry we skip
+fn up_migrations() -> impl Iterator<Item = &'static sqlx::migrate::Migration> {
+    MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+}
+
+/// Applies every pending migration
+pub async fn up(pool: &PgPool) -> Result<(), MigrateError> {
+    MIGRATOR.run(pool).await
+}
+
+/// Every known migration and whether it has been applied
+pub async fn status(pool: &PgPool) -> Result<Vec<MigrationStatus>, MigrateError> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let applied: HashSet<i64> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+    Ok(up_migrations()
+        .map(|migration| MigrationStatus {
+            version: migration.version,
+            description: migration.description.to_string(),
+            applied: applied.contains(&migration.version),
+        })
+        .collect())
+}
+
+/// Reverts the newest applied migration, returns its version
+pub async fn down(pool: &PgPool) -> Result<Option<i64>, MigrateError> {
+    let applied: Vec<i64> = status(pool)
+        .await?
+        .into_iter()
+        .filter(|migration| migration.applied)
+        .map(|migration| migration.version)
+        .collect();
+    let Some((latest, rest)) = applied.split_last() else {
+        return Ok(None);
+    };
+    let reversible = MIGRATOR.iter().any(|migration| {
+        migration.version == *latest
+            && migration.migration_type == MigrationType::ReversibleDown
+    });
+    if !reversible {
+        return Err(MigrateError::VersionMissing(*latest));
+    }
+    MIGRATOR.undo(pool, rest.last().copied().unwrap_or(0)).await?;
+    Ok(Some(*latest))
+}
@@ -19,6 +19,74 @@ pub struct IdentifiableTeam {
     pub name: String
 }
 
+// The team's invite code, only shown to captains
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(FromRow)]
+pub struct TeamInvite {
+    pub team_id: i32,
+    pub code: String,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub max_uses: Option<i32>,
+    pub uses: i32
+}
+
+impl TeamInvite {
+    pub fn is_expired(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    pub fn is_used_up(&self) -> bool {
+        self.max_uses.is_some_and(|max_uses| self.uses >= max_uses)
+    }
+}
+
+// The secret token of the team's calendar feed, shown to everyone on the roster
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(FromRow)]
+pub struct TeamFeed {
+    pub team_id: i32,
+    pub token: String
+}
+
+// The secret token of the player's calendar feed, shown only to the player
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(FromRow)]
+pub struct PlayerFeed {
+    pub player_id: i32,
+    pub token: String
+}
+
+// Connections of the database pool, for readiness checks
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct PoolStatus {
+    pub size: u32,
+    pub idle: u32,
+    pub max: u32,
+}
+
+// Body of /readyz, `error` says why the store can't serve queries
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Readiness {
+    pub ready: bool,
+    pub error: Option<String>,
+    pub pool: Option<PoolStatus>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VersionInfo {
+    pub version: String,
+    pub git_sha: String,
+    // None when the database is unreachable or has no migrations table
+    pub migration: Option<i64>,
+}
+
+// Body of an invite rotation, without limits the code works until rotated or revoked
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct InviteOptions {
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub max_uses: Option<i32>
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct User {
     pub name: String
@@ -32,6 +100,40 @@ pub struct IdentifiableUser {
     pub name: String
 }
 
+// Body of register and login, deliberately not Debug so the password can't end up in a log
+#[derive(Deserialize, Clone)]
+pub struct Credentials {
+    pub name: String,
+    pub password: String
+}
+
+// A user with their argon2 hash, never serialized
+#[derive(Debug, Clone)]
+#[derive(FromRow)]
+pub struct UserCredentials {
+    pub id: i32,
+    pub name: String,
+    pub password_hash: Option<String>
+}
+
+#[derive(Debug, Clone)]
+#[derive(FromRow)]
+pub struct Session {
+    // SHA-256 of the token, hex encoded
+    pub token_hash: String,
+    pub user_id: i32,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: chrono::DateTime<chrono::Utc>
+}
+
+// Returned by register and login, the token is only ever shown here
+#[derive(Serialize, Debug, Clone)]
+pub struct AuthSession {
+    pub token: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub user: IdentifiableUser
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Player {
     pub user_id: i32
@@ -45,18 +147,170 @@ pub struct IdentifiablePlayer {
     pub user_id: i32
 }
 
+// Declared lowest to highest so roles compare by how much they allow
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TeamRole {
+    #[default]
+    Member,
+    Captain,
+    Owner,
+}
+
+impl TeamRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TeamRole::Member => "member",
+            TeamRole::Captain => "captain",
+            TeamRole::Owner => "owner",
+        }
+    }
+}
+
+impl std::fmt::Display for TeamRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for TeamRole {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "member" => Ok(TeamRole::Member),
+            "captain" => Ok(TeamRole::Captain),
+            "owner" => Ok(TeamRole::Owner),
+            other => Err(format!("unknown team role `{}`", other)),
+        }
+    }
+}
+
+// Enums stored in a text column guarded by a check constraint, going through as_str and FromStr
+macro_rules! text_column {
+    ($name:ty) => {
+        impl sqlx::Type<sqlx::Postgres> for $name {
+            fn type_info() -> sqlx::postgres::PgTypeInfo {
+                <String as sqlx::Type<sqlx::Postgres>>::type_info()
+            }
+
+            fn compatible(ty: &sqlx::postgres::PgTypeInfo) -> bool {
+                <String as sqlx::Type<sqlx::Postgres>>::compatible(ty)
+            }
+        }
+
+        impl sqlx::Encode<'_, sqlx::Postgres> for $name {
+            fn encode_by_ref(
+                &self,
+                buf: &mut sqlx::postgres::PgArgumentBuffer,
+            ) -> sqlx::encode::IsNull {
+                <&str as sqlx::Encode<sqlx::Postgres>>::encode(self.as_str(), buf)
+            }
+        }
+
+        impl sqlx::Decode<'_, sqlx::Postgres> for $name {
+            fn decode(
+                value: sqlx::postgres::PgValueRef<'_>,
+            ) -> Result<Self, sqlx::error::BoxDynError> {
+                Ok(<&str as sqlx::Decode<sqlx::Postgres>>::decode(value)?.parse()?)
+            }
+        }
+    };
+}
+
+text_column!(TeamRole);
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[derive(FromRow)]
-//TODO: Figure out if I'm using this
 pub struct PlayerToTeam {
     pub player_id: i32,
-    pub team_id: i32
+    pub team_id: i32,
+    #[serde(default)]
+    pub role: TeamRole
 }
 
-
+// A player on a team's roster with the user's name joined in. camelCase like the availability
+// and suggestion bodies it is nested in
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(FromRow)]
 #[serde(rename_all = "camelCase")]
+pub struct RosterMember {
+    pub player_id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub role: TeamRole
+}
+
+// Body of the roster role update
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RoleChange {
+    pub role: TeamRole
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum JoinRequestStatus {
+    #[default]
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl JoinRequestStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JoinRequestStatus::Pending => "pending",
+            JoinRequestStatus::Approved => "approved",
+            JoinRequestStatus::Rejected => "rejected",
+        }
+    }
+}
+
+impl std::str::FromStr for JoinRequestStatus {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "pending" => Ok(JoinRequestStatus::Pending),
+            "approved" => Ok(JoinRequestStatus::Approved),
+            "rejected" => Ok(JoinRequestStatus::Rejected),
+            other => Err(format!("unknown join request status `{}`", other)),
+        }
+    }
+}
+
+text_column!(JoinRequestStatus);
+
+// A player asking to be put on a team's roster, approving it adds them as a member
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[derive(FromRow)]
+pub struct JoinRequest {
+    pub id: i32,
+    pub team_id: i32,
+    pub player_id: i32,
+    pub status: JoinRequestStatus,
+    pub message: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub resolved_at: Option<chrono::DateTime<chrono::Utc>>,
+    // The user who approved or rejected it
+    pub resolved_by: Option<i32>
+}
+
+// Body of a new join request
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct NewJoinRequest {
+    pub message: Option<String>
+}
+
+// Body of an approval or rejection
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JoinRequestResolution {
+    pub status: JoinRequestStatus
+}
+
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct AvailableBlock {
     #[serde(
         deserialize_with = "deserialize_naive_time", //TODO: Update Chrono types is changed
@@ -67,25 +321,40 @@ pub struct AvailableBlock {
         deserialize_with = "deserialize_naive_time", //TODO: Update Chrono types is changed
         serialize_with = "serialize_naive_time"
     )]
+    // Before start_time for blocks that end the next day, e.g. 22:00 to 02:00
     pub end_time: chrono::NaiveTime,
     pub need_warning: bool,
     #[serde(
         deserialize_with = "deserialize_rrule_set",
         serialize_with = "serialize_rrule_set"
     )]
-    #[sqlx(try_from = "String")]
     pub repeats: MyRRuleSet, // TODO: Decide if optional, if so add default derive
-    pub player_id: i32
+    pub player_id: i32,
+    // IANA timezone start_time and end_time are given in, the rule only supplies the dates
+    #[serde(default = "default_timezone")]
+    pub timezone: chrono_tz::Tz
 }
 
-// Wrapper Type for RRulset To implment From<String>
+impl AvailableBlock {
+    // A block whose end is before its start runs past midnight into the next day
+    pub fn is_overnight(&self) -> bool {
+        self.end_time < self.start_time
+    }
+}
+
+fn default_timezone() -> chrono_tz::Tz {
+    chrono_tz::UTC
+}
+
+// Wrapper Type for RRulset To implment TryFrom<String>
 #[derive(Debug, Clone)]
 pub struct MyRRuleSet(RRuleSet);
 
-impl From<String> for MyRRuleSet {
-    fn from(value: String) -> Self {
-        // TODO: Not use unwrap.
-        MyRRuleSet(value.parse().unwrap())
+impl TryFrom<String> for MyRRuleSet {
+    type Error = rrule::RRuleError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse().map(MyRRuleSet)
     }
 }
 
@@ -106,15 +375,185 @@ impl std::ops::DerefMut for MyRRuleSet {
 //TODO: Remove Debug, Clone
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-#[derive(FromRow)]
 pub struct IdentifiableAvailableBlock {
     pub id: i32,
     #[serde(flatten)]
-    #[sqlx(flatten)]
     pub inner_block: AvailableBlock
 }
 
 
+// An available block row as stored, before its rule has been parsed. The time and rule
+// columns are nullable in the schema, a NULL is reported like any other bad value.
+#[derive(Debug, Clone)]
+#[derive(FromRow)]
+pub struct RawAvailableBlock {
+    pub id: i32,
+    pub start_time: Option<chrono::NaiveTime>,
+    pub end_time: Option<chrono::NaiveTime>,
+    pub need_warning: bool,
+    pub repeats: Option<String>,
+    pub player_id: i32,
+    pub timezone: String
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum BlockDecodeError {
+    #[error("{0} is NULL")]
+    Null(&'static str),
+    #[error(transparent)]
+    Rule(#[from] rrule::RRuleError),
+    #[error("unknown timezone `{0}`")]
+    Timezone(String),
+}
+
+impl TryFrom<RawAvailableBlock> for IdentifiableAvailableBlock {
+    type Error = BlockDecodeError;
+
+    fn try_from(value: RawAvailableBlock) -> Result<Self, Self::Error> {
+        let timezone = value
+            .timezone
+            .parse()
+            .map_err(|_| BlockDecodeError::Timezone(value.timezone.clone()))?;
+        Ok(IdentifiableAvailableBlock {
+            id: value.id,
+            inner_block: AvailableBlock {
+                start_time: value.start_time.ok_or(BlockDecodeError::Null("start_time"))?,
+                end_time: value.end_time.ok_or(BlockDecodeError::Null("end_time"))?,
+                need_warning: value.need_warning,
+                repeats: value.repeats.ok_or(BlockDecodeError::Null("repeats"))?.try_into()?,
+                player_id: value.player_id,
+                timezone
+            }
+        })
+    }
+}
+
+// A stored block with a NULL time or rule, or whose rule or timezone no longer parses
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InvalidAvailableBlock {
+    pub id: i32,
+    pub player_id: i32,
+    pub repeats: Option<String>,
+    pub timezone: String,
+    pub error: String
+}
+
+// A practice or match on the team's schedule, also the body of event creation and updates
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TeamEvent {
+    pub title: String,
+    pub start: chrono::DateTime<chrono::Utc>,
+    // End of the first occurrence, every later one lasts as long
+    pub end: chrono::DateTime<chrono::Utc>,
+    // Gives the start of every occurrence, its DTSTART must be `start`
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_rrule_set",
+        serialize_with = "serialize_optional_rrule_set"
+    )]
+    pub repeats: Option<MyRRuleSet>,
+    pub location: Option<String>,
+    pub notes: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdentifiableTeamEvent {
+    pub id: i32,
+    pub team_id: i32,
+    #[serde(flatten)]
+    pub inner_event: TeamEvent,
+    // The user who scheduled it
+    pub created_by: Option<i32>,
+    pub created_at: chrono::DateTime<chrono::Utc>
+}
+
+// A team event row as stored, before its rule has been parsed
+#[derive(Debug, Clone)]
+#[derive(FromRow)]
+pub struct RawTeamEvent {
+    pub id: i32,
+    pub team_id: i32,
+    pub title: String,
+    pub starts_at: chrono::DateTime<chrono::Utc>,
+    pub ends_at: chrono::DateTime<chrono::Utc>,
+    pub repeats: Option<String>,
+    pub location: Option<String>,
+    pub notes: Option<String>,
+    pub created_by: Option<i32>,
+    pub created_at: chrono::DateTime<chrono::Utc>
+}
+
+impl TryFrom<RawTeamEvent> for IdentifiableTeamEvent {
+    type Error = rrule::RRuleError;
+
+    fn try_from(value: RawTeamEvent) -> Result<Self, Self::Error> {
+        Ok(IdentifiableTeamEvent {
+            id: value.id,
+            team_id: value.team_id,
+            inner_event: TeamEvent {
+                title: value.title,
+                start: value.starts_at,
+                end: value.ends_at,
+                repeats: value.repeats.map(MyRRuleSet::try_from).transpose()?,
+                location: value.location,
+                notes: value.notes
+            },
+            created_by: value.created_by,
+            created_at: value.created_at
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RsvpResponse {
+    Yes,
+    No,
+    Maybe,
+}
+
+impl RsvpResponse {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RsvpResponse::Yes => "yes",
+            RsvpResponse::No => "no",
+            RsvpResponse::Maybe => "maybe",
+        }
+    }
+}
+
+impl std::str::FromStr for RsvpResponse {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "yes" => Ok(RsvpResponse::Yes),
+            "no" => Ok(RsvpResponse::No),
+            "maybe" => Ok(RsvpResponse::Maybe),
+            other => Err(format!("unknown rsvp response `{}`", other)),
+        }
+    }
+}
+
+text_column!(RsvpResponse);
+
+// A player's answer to a team event, covers every occurrence of a repeating event
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(FromRow)]
+pub struct Rsvp {
+    pub event_id: i32,
+    pub player_id: i32,
+    pub response: RsvpResponse,
+    pub updated_at: chrono::DateTime<chrono::Utc>
+}
+
+// Body of an rsvp
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RsvpChange {
+    pub response: RsvpResponse
+}
+
 
 fn serialize_rrule_set<S>(x: &RRuleSet, serializer: S) -> Result<S::Ok, S::Error>
 where
@@ -134,6 +573,23 @@ D: Deserializer<'de>,
     }).map_err(Error::custom)
 }
 
+fn serialize_optional_rrule_set<S>(x: &Option<MyRRuleSet>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer
+{
+    serde::Serialize::serialize(&x.as_ref().map(|rruleset| rruleset.to_string()), serializer)
+}
+
+fn deserialize_optional_rrule_set<'de, D>(deserializer: D) -> Result<Option<MyRRuleSet>, D::Error>
+where
+D: Deserializer<'de>,
+{
+    use serde::de::Error;
+    Option::<String>::deserialize(deserializer)?
+        .map(|rrule_string| rrule_string.parse::<RRuleSet>().map(MyRRuleSet).map_err(Error::custom))
+        .transpose()
+}
+
 
 fn serialize_naive_time<S>(x: &chrono::NaiveTime, serializer: S) -> Result<S::Ok, S::Error>
 where
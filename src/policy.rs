@@ -0,0 +1,156 @@
+use crate::api::DynAvailStore;
+use crate::error::Error;
+use crate::model::*;
+
+type Result<T, E = Error> = core::result::Result<T, E>;
+
+/// Decides what the current user may change or see, every mutating handler asks here first.
+/// Lookups that fail with a missing team or player report 404 before any 403.
+pub struct Policy<'a> {
+    store: &'a DynAvailStore,
+    user: &'a IdentifiableUser,
+}
+
+fn forbidden(message: impl Into<String>) -> Error {
+    Error::Forbidden(message.into())
+}
+
+impl<'a> Policy<'a> {
+    pub fn new(store: &'a DynAvailStore, user: &'a IdentifiableUser) -> Self {
+        Policy { store, user }
+    }
+
+    // The player belonging to the current user, if they made one
+    pub async fn current_player(&self) -> Result<Option<IdentifiablePlayer>> {
+        Ok(self.store.get_player_by_user_id(self.user.id).await?)
+    }
+
+    async fn team_role(&self, team_id: i32) -> Result<Option<TeamRole>> {
+        if self.store.get_team_by_id(team_id).await?.is_none() {
+            return Err(Error::NotFound("team"));
+        }
+        let Some(player) = self.current_player().await? else {
+            return Ok(None);
+        };
+        Ok(self
+            .store
+            .get_team_membership(team_id, player.id)
+            .await?
+            .map(|membership| membership.role))
+    }
+
+    pub async fn require_admin(&self) -> Result<()> {
+        if !self.store.is_admin(self.user.id).await? {
+            return Err(forbidden("this requires an admin"));
+        }
+        Ok(())
+    }
+
+    pub fn require_self(&self, user_id: i32) -> Result<()> {
+        if self.user.id != user_id {
+            return Err(forbidden("you can only change your own user"));
+        }
+        Ok(())
+    }
+
+    /// Passes when the current user holds `role` or a higher one on the team
+    pub async fn require_team_role(&self, team_id: i32, role: TeamRole) -> Result<()> {
+        match self.team_role(team_id).await? {
+            Some(held) if held >= role => Ok(()),
+            _ => Err(forbidden(format!(
+                "this requires the {} role on the team",
+                role
+            ))),
+        }
+    }
+
+    pub async fn require_own_player(&self, player_id: i32) -> Result<()> {
+        let player = self
+            .store
+            .get_player_by_id(player_id)
+            .await?
+            .ok_or(Error::NotFound("player"))?;
+        if player.user_id != self.user.id {
+            return Err(forbidden("you can only change your own player"));
+        }
+        Ok(())
+    }
+
+    /// Players edit their own blocks, captains and owners edit the blocks of anyone on their teams
+    pub async fn require_block_editor(&self, player_id: i32) -> Result<()> {
+        let Some(current) = self.current_player().await? else {
+            return Err(forbidden("you need a player to edit available blocks"));
+        };
+        if current.id == player_id {
+            return Ok(());
+        }
+        let led_teams: Vec<i32> = self
+            .store
+            .get_memberships_by_player_id(current.id)
+            .await?
+            .into_iter()
+            .filter(|membership| membership.role >= TeamRole::Captain)
+            .map(|membership| membership.team_id)
+            .collect();
+        let shares_led_team = self
+            .store
+            .get_memberships_by_player_id(player_id)
+            .await?
+            .iter()
+            .any(|membership| led_teams.contains(&membership.team_id));
+        if !shares_led_team {
+            return Err(forbidden(
+                "only captains can edit the available blocks of their teammates",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Players see their own blocks and teams, and those of anyone they share a team with
+    pub async fn require_player_viewer(&self, player_id: i32) -> Result<()> {
+        if self.store.get_player_by_id(player_id).await?.is_none() {
+            return Err(Error::NotFound("player"));
+        }
+        let Some(current) = self.current_player().await? else {
+            return Err(forbidden("you need a player to see other players"));
+        };
+        if current.id == player_id {
+            return Ok(());
+        }
+        let teams: Vec<i32> = self
+            .store
+            .get_memberships_by_player_id(current.id)
+            .await?
+            .into_iter()
+            .map(|membership| membership.team_id)
+            .collect();
+        let shares_team = self
+            .store
+            .get_memberships_by_player_id(player_id)
+            .await?
+            .iter()
+            .any(|membership| teams.contains(&membership.team_id));
+        if !shares_team {
+            return Err(forbidden(
+                "you can only see your own player and your teammates",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Anyone but the owner may leave, captains remove members and only the owner removes captains
+    pub async fn require_can_remove(&self, target: &PlayerToTeam) -> Result<()> {
+        if target.role == TeamRole::Owner {
+            return Err(forbidden("the owner can't leave the team, delete it instead"));
+        }
+        let current = self.current_player().await?;
+        if current.is_some_and(|player| player.id == target.player_id) {
+            return Ok(());
+        }
+        let needed = match target.role {
+            TeamRole::Member => TeamRole::Captain,
+            _ => TeamRole::Owner,
+        };
+        self.require_team_role(target.team_id, needed).await
+    }
+}
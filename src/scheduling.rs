@@ -0,0 +1,339 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+
+use crate::availability::{self, AvailabilityInterval, Interval};
+use crate::model::*;
+
+#[derive(Debug, Clone)]
+pub struct SlotCriteria {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub min_players: usize,
+    pub min_duration: Duration,
+    pub limit: usize,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SlotSuggestion {
+    pub rank: usize,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub duration_minutes: i64,
+    pub available_players: Vec<RosterMember>,
+    pub missing_players: Vec<RosterMember>,
+    // Available players who asked to be warned before being scheduled in this slot
+    pub warning_player_ids: Vec<i32>,
+}
+
+#[derive(Debug, Clone)]
+struct Candidate {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    player_ids: BTreeSet<i32>,
+}
+
+impl Candidate {
+    fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+
+    // Another candidate covers at least the same time with at least the same players
+    fn dominated_by(&self, other: &Candidate) -> bool {
+        other.start <= self.start
+            && other.end >= self.end
+            && other.player_ids.is_superset(&self.player_ids)
+    }
+}
+
+/// Walks forward from every segment start and records the longest window for each set of
+/// players that stays free the whole time, as long as at least `min_players` remain.
+fn find_candidates(segments: &[AvailabilityInterval], min_players: usize) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    for (i, first) in segments.iter().enumerate() {
+        let mut players: BTreeSet<i32> = first.player_ids.iter().copied().collect();
+        if players.len() < min_players {
+            continue;
+        }
+        let mut end = first.end;
+        for next in &segments[i + 1..] {
+            if next.start != end {
+                break;
+            }
+            let remaining: BTreeSet<i32> = players
+                .intersection(&next.player_ids.iter().copied().collect())
+                .copied()
+                .collect();
+            if remaining.len() < players.len() {
+                // The set is about to shrink, this is the longest window for the current set
+                candidates.push(Candidate {
+                    start: first.start,
+                    end,
+                    player_ids: players.clone(),
+                });
+            }
+            if remaining.len() < min_players {
+                break;
+            }
+            players = remaining;
+            end = next.end;
+        }
+        if players.len() >= min_players {
+            candidates.push(Candidate {
+                start: first.start,
+                end,
+                player_ids: players,
+            });
+        }
+    }
+    candidates
+}
+
+fn overlaps(intervals: &[Interval], start: DateTime<Utc>, end: DateTime<Utc>) -> bool {
+    intervals
+        .iter()
+        .any(|interval| interval.start < end && interval.end > start)
+}
+
+/// Ranks the windows where at least `min_players` of the roster are free for at least
+/// `min_duration`. More players ranks first, then longer windows, then fewer players
+/// needing a warning, then earlier start.
+pub fn suggest_slots(
+    players: &[RosterMember],
+    blocks: &[IdentifiableAvailableBlock],
+    criteria: &SlotCriteria,
+) -> Vec<SlotSuggestion> {
+    let per_player = availability::expand_blocks_by_player(blocks, criteria.from, criteria.to);
+    let warning_blocks: Vec<IdentifiableAvailableBlock> = blocks
+        .iter()
+        .filter(|block| block.inner_block.need_warning)
+        .cloned()
+        .collect();
+    let warnings: BTreeMap<i32, Vec<Interval>> =
+        availability::expand_blocks_by_player(&warning_blocks, criteria.from, criteria.to);
+
+    let segments = availability::merge_availability(&per_player);
+    let mut candidates: Vec<Candidate> = find_candidates(&segments, criteria.min_players.max(1))
+        .into_iter()
+        .filter(|candidate| candidate.duration() >= criteria.min_duration)
+        .collect();
+
+    candidates.sort_by_key(|candidate| {
+        (
+            Reverse(candidate.player_ids.len()),
+            Reverse(candidate.duration()),
+            candidate.start,
+        )
+    });
+    let mut kept: Vec<Candidate> = Vec::new();
+    for candidate in candidates {
+        if !kept.iter().any(|other| candidate.dominated_by(other)) {
+            kept.push(candidate);
+        }
+    }
+
+    let mut ranked: Vec<(Candidate, Vec<i32>)> = kept
+        .into_iter()
+        .map(|candidate| {
+            let warning_player_ids = candidate
+                .player_ids
+                .iter()
+                .filter(|player_id| {
+                    warnings.get(player_id).is_some_and(|intervals| {
+                        overlaps(intervals, candidate.start, candidate.end)
+                    })
+                })
+                .copied()
+                .collect();
+            (candidate, warning_player_ids)
+        })
+        .collect();
+    ranked.sort_by_key(|(candidate, warning_player_ids)| {
+        (
+            Reverse(candidate.player_ids.len()),
+            Reverse(candidate.duration()),
+            warning_player_ids.len(),
+            candidate.start,
+        )
+    });
+
+    ranked
+        .into_iter()
+        .take(criteria.limit)
+        .enumerate()
+        .map(|(index, (candidate, warning_player_ids))| {
+            let (available_players, missing_players) = players
+                .iter()
+                .cloned()
+                .partition(|player| candidate.player_ids.contains(&player.player_id));
+            SlotSuggestion {
+                rank: index + 1,
+                start: candidate.start,
+                end: candidate.end,
+                duration_minutes: candidate.duration().num_minutes(),
+                available_players,
+                missing_players,
+                warning_player_ids,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveTime, TimeZone};
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 19, hour, minute, 0).unwrap()
+    }
+
+    fn member(player_id: i32) -> RosterMember {
+        RosterMember {
+            player_id,
+            user_id: player_id,
+            name: format!("player {}", player_id),
+            role: TeamRole::Member,
+        }
+    }
+
+    // A daily block between two (hour, minute) times in UTC
+    fn block(
+        player_id: i32,
+        start: (u32, u32),
+        end: (u32, u32),
+        need_warning: bool,
+    ) -> IdentifiableAvailableBlock {
+        let repeats = "DTSTART:20261019T000000Z\nRRULE:FREQ=DAILY".to_string();
+        IdentifiableAvailableBlock {
+            id: 0,
+            inner_block: AvailableBlock {
+                start_time: NaiveTime::from_hms_opt(start.0, start.1, 0).unwrap(),
+                end_time: NaiveTime::from_hms_opt(end.0, end.1, 0).unwrap(),
+                need_warning,
+                repeats: MyRRuleSet::try_from(repeats).unwrap(),
+                player_id,
+                timezone: chrono_tz::UTC,
+            },
+        }
+    }
+
+    fn criteria(min_players: usize, min_minutes: i64) -> SlotCriteria {
+        SlotCriteria {
+            from: at(0, 0),
+            to: at(23, 59),
+            min_players,
+            min_duration: Duration::minutes(min_minutes),
+            limit: 10,
+        }
+    }
+
+    fn windows(suggestions: &[SlotSuggestion]) -> Vec<(DateTime<Utc>, DateTime<Utc>, Vec<i32>)> {
+        suggestions
+            .iter()
+            .map(|suggestion| {
+                let players = suggestion
+                    .available_players
+                    .iter()
+                    .map(|player| player.player_id)
+                    .collect();
+                (suggestion.start, suggestion.end, players)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ties_prefer_fewer_warning_players() {
+        let players = [member(1), member(2)];
+        let blocks = [
+            block(1, (10, 0), (12, 0), true),
+            block(1, (14, 0), (16, 0), false),
+            block(2, (10, 0), (12, 0), false),
+            block(2, (14, 0), (16, 0), false),
+        ];
+        let suggestions = suggest_slots(&players, &blocks, &criteria(2, 60));
+        // Same players and length, the later window wins because nobody needs a warning
+        assert_eq!(
+            windows(&suggestions),
+            vec![
+                (at(14, 0), at(16, 0), vec![1, 2]),
+                (at(10, 0), at(12, 0), vec![1, 2]),
+            ]
+        );
+        assert!(suggestions[0].warning_player_ids.is_empty());
+        assert_eq!(suggestions[1].warning_player_ids, vec![1]);
+        assert_eq!(suggestions[1].rank, 2);
+    }
+
+    #[test]
+    fn dominated_candidates_are_dropped() {
+        let candidate = |start: u32, end: u32, players: &[i32]| Candidate {
+            start: at(start, 0),
+            end: at(end, 0),
+            player_ids: players.iter().copied().collect(),
+        };
+        let wide = candidate(10, 14, &[1, 2]);
+        assert!(candidate(11, 13, &[1]).dominated_by(&wide));
+        assert!(candidate(10, 14, &[1, 2]).dominated_by(&wide));
+        assert!(!candidate(9, 13, &[1]).dominated_by(&wide));
+        assert!(!candidate(11, 13, &[1, 3]).dominated_by(&wide));
+
+        // Walking on from 11:00 finds player 1 alone until 14:00, inside their 10:00 window
+        let players = [member(1), member(2)];
+        let blocks = [
+            block(1, (10, 0), (14, 0), false),
+            block(2, (11, 0), (13, 0), false),
+        ];
+        let suggestions = suggest_slots(&players, &blocks, &criteria(1, 60));
+        assert_eq!(
+            windows(&suggestions),
+            vec![
+                (at(11, 0), at(13, 0), vec![1, 2]),
+                (at(10, 0), at(14, 0), vec![1]),
+            ]
+        );
+        assert_eq!(suggestions[1].missing_players[0].player_id, 2);
+    }
+
+    #[test]
+    fn windows_under_min_duration_are_left_out() {
+        let players = [member(1), member(2)];
+        let blocks = [
+            block(1, (10, 0), (12, 0), false),
+            block(2, (11, 30), (13, 0), false),
+        ];
+        assert!(suggest_slots(&players, &blocks, &criteria(2, 60)).is_empty());
+        let suggestions = suggest_slots(&players, &blocks, &criteria(2, 30));
+        assert_eq!(windows(&suggestions), vec![(at(11, 30), at(12, 0), vec![1, 2])]);
+        assert_eq!(suggestions[0].duration_minutes, 30);
+    }
+
+    #[test]
+    fn windows_under_min_players_are_left_out() {
+        let players = [member(1), member(2), member(3)];
+        let blocks = [
+            block(1, (10, 0), (14, 0), false),
+            block(2, (11, 0), (14, 0), false),
+            block(3, (12, 0), (13, 0), false),
+        ];
+        let suggestions = suggest_slots(&players, &blocks, &criteria(3, 60));
+        assert_eq!(windows(&suggestions), vec![(at(12, 0), at(13, 0), vec![1, 2, 3])]);
+
+        let suggestions = suggest_slots(&players, &blocks, &criteria(2, 60));
+        assert_eq!(
+            windows(&suggestions),
+            vec![
+                (at(12, 0), at(13, 0), vec![1, 2, 3]),
+                (at(11, 0), at(14, 0), vec![1, 2]),
+            ]
+        );
+
+        // Zero counts as one, a window always has someone in it
+        let suggestions = suggest_slots(&players, &blocks, &criteria(0, 60));
+        assert_eq!(suggestions.last().unwrap().available_players.len(), 1);
+        assert!(suggestions.iter().all(|suggestion| !suggestion.available_players.is_empty()));
+    }
+}
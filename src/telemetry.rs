@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use axum::{
+    extract::MatchedPath,
+    http::{HeaderName, Request, Response},
+    Router,
+};
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::{MakeSpan, OnResponse, TraceLayer};
+use tracing::{field, Span};
+use tracing_subscriber::fmt::format::FmtSpan;
+
+use crate::config::{ConfigError, LogConfig, LogFormat};
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Installs the global subscriber. Spans log a line when they close, with how long they were busy,
+/// which is how requests and store queries are timed.
+pub fn init(config: &LogConfig) -> Result<(), ConfigError> {
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(config.env_filter()?)
+        .with_span_events(FmtSpan::CLOSE);
+    match config.format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+    Ok(())
+}
+
+/// Gives every request an `X-Request-Id`, keeping the one a client sent, returns it on the
+/// response and runs the request in a span carrying it, so a bug report with the id can be
+/// matched to every log line of that request
+pub fn trace_requests(router: Router) -> Router {
+    router
+        .layer(PropagateRequestIdLayer::new(REQUEST_ID_HEADER))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(RequestSpan)
+                .on_request(())
+                .on_response(RecordResponse),
+        )
+        .layer(SetRequestIdLayer::new(REQUEST_ID_HEADER, MakeRequestUuid))
+}
+
+#[derive(Clone, Copy)]
+struct RequestSpan;
+
+impl<B> MakeSpan<B> for RequestSpan {
+    fn make_span(&mut self, request: &Request<B>) -> Span {
+        let request_id = request
+            .headers()
+            .get(&REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        // The route template instead of the path, feed urls carry their secret token
+        let route = request
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|path| path.as_str())
+            .unwrap_or_else(|| request.uri().path());
+        tracing::info_span!(
+            "request",
+            method = %request.method(),
+            route,
+            request_id,
+            status = field::Empty,
+            latency_ms = field::Empty,
+        )
+    }
+}
+
+// Adds the outcome to the request span instead of logging a line of its own
+#[derive(Clone, Copy)]
+struct RecordResponse;
+
+impl<B> OnResponse<B> for RecordResponse {
+    fn on_response(self, response: &Response<B>, latency: Duration, span: &Span) {
+        span.record("status", response.status().as_u16());
+        span.record("latency_ms", latency.as_millis() as u64);
+    }
+}
@@ -0,0 +1,1633 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+use team_availablity_coordinator::api::{api_routes, health_routes, DynAvailStore};
+use team_availablity_coordinator::memory::InMemoryAvailablityStore;
+use team_availablity_coordinator::metrics;
+use team_availablity_coordinator::telemetry;
+
+mod common;
+
+use common::*;
+
+fn app() -> Router {
+    let store = Arc::new(InMemoryAvailablityStore::new()) as DynAvailStore;
+    api_routes(store)
+}
+
+#[tokio::test]
+async fn problem_responses_use_problem_json() {
+    let app = app();
+    let request = Request::builder()
+        .uri("/team/by-id/1")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/problem+json"
+    );
+}
+
+#[tokio::test]
+async fn team_crud() {
+    let app = app();
+    let alice = create_player(&app, "alice").await;
+    let id = create_team(&app, &alice, "falcons").await;
+
+    let (status, team) = get(&app, &format!("/team/by-id/{}", id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(team["name"], "falcons");
+
+    let (status, team) = get(&app, "/team/by-name/falcons").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(team["id"], id);
+
+    let (status, team) = send_as(
+        &app,
+        Some(&alice.token),
+        Method::PATCH,
+        &format!("/team/by-id/{}", id),
+        Some(json!({ "id": id, "name": "hawks" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(team["name"], "hawks");
+    let (_, team) = get(&app, "/team/by-name/hawks").await;
+    assert_eq!(team["id"], id);
+
+    // The owner can delete the team with its members still on it
+    let bob = create_player(&app, "bob").await;
+    join_team(&app, &alice, id, bob.id).await;
+    let (status, _) = send_as(
+        &app,
+        Some(&alice.token),
+        Method::DELETE,
+        &format!("/team/by-id/{}", id),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, body) = get(&app, &format!("/team/by-id/{}", id)).await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+    let (_, teams) = get_as(&app, &bob.token, &format!("/player/{}/teams", bob.id)).await;
+    assert_eq!(teams, json!([]));
+}
+
+#[tokio::test]
+async fn team_errors() {
+    let app = app();
+    let alice = create_player(&app, "alice").await;
+    create_team(&app, &alice, "falcons").await;
+
+    let (status, body) = post_as(&app, &alice.token, "/team/create", json!({ "name": "falcons" })).await;
+    assert_problem(status, &body, StatusCode::CONFLICT, "already_exists");
+
+    let (status, body) = post_as(&app, &alice.token, "/team/create", json!({ "name": " " })).await;
+    assert_problem(status, &body, StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+
+    let (status, body) = post_as(&app, &alice.token, "/team/create", json!({ "nam": "falcons" })).await;
+    assert_problem(status, &body, StatusCode::UNPROCESSABLE_ENTITY, "invalid_body");
+
+    let (status, body) = post(&app, "/team/create", json!({ "name": "hawks" })).await;
+    assert_problem(status, &body, StatusCode::UNAUTHORIZED, "unauthorized");
+
+    // Teams are owned by a player
+    let (_, token) = register(&app, "carol").await;
+    let (status, body) = post_as(&app, &token, "/team/create", json!({ "name": "hawks" })).await;
+    assert_problem(status, &body, StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+
+    let (status, body) = get(&app, "/team/by-name/nobody").await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+
+    let (status, body) = get(&app, "/team/by-id/abc").await;
+    assert_problem(status, &body, StatusCode::BAD_REQUEST, "invalid_path");
+
+    let (status, body) = send_as(
+        &app,
+        Some(&alice.token),
+        Method::PATCH,
+        "/team/by-id/99",
+        Some(json!({ "id": 99, "name": "ghosts" })),
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+}
+
+#[tokio::test]
+async fn team_roles() {
+    let app = app();
+    let alice = create_player(&app, "alice").await;
+    let bob = create_player(&app, "bob").await;
+    let carol = create_player(&app, "carol").await;
+    let team_id = create_team(&app, &alice, "falcons").await;
+    join_team(&app, &alice, team_id, bob.id).await;
+    join_team(&app, &alice, team_id, carol.id).await;
+
+    let (_, roster) = get_as(&app, &alice.token, &format!("/team/by-id/{}/roster", team_id)).await;
+    let roles: Vec<&str> = roster
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|member| member["role"].as_str().unwrap())
+        .collect();
+    assert_eq!(roles, vec!["owner", "member", "member"]);
+
+    // Members can't rename, delete or manage the roster
+    let rename = Some(json!({ "id": team_id, "name": "hawks" }));
+    let team_uri = format!("/team/by-id/{}", team_id);
+    let (status, body) = send_as(&app, Some(&bob.token), Method::PATCH, &team_uri, rename.clone()).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+    let (status, body) = send_as(&app, Some(&bob.token), Method::DELETE, &team_uri, None).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+    let carol_uri = format!("/team/by-id/{}/roster/{}", team_id, carol.id);
+    let (status, body) = send_as(&app, Some(&bob.token), Method::DELETE, &carol_uri, None).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+    let (status, body) = post_as(
+        &app,
+        &bob.token,
+        "/available-blocks/create",
+        json!({
+            "startTime": "18:00:00",
+            "endTime": "22:00:00",
+            "needWarning": false,
+            "repeats": DAILY,
+            "playerId": carol.id,
+        }),
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+
+    // Only the owner hands out roles
+    let bob_uri = format!("/team/by-id/{}/roster/{}", team_id, bob.id);
+    let captain = Some(json!({ "role": "captain" }));
+    let (status, body) = send_as(&app, Some(&bob.token), Method::PATCH, &bob_uri, captain.clone()).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+    let (status, membership) = send_as(&app, Some(&alice.token), Method::PATCH, &bob_uri, captain).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(membership["role"], "captain");
+    let (status, body) = send_as(
+        &app,
+        Some(&alice.token),
+        Method::PATCH,
+        &bob_uri,
+        Some(json!({ "role": "owner" })),
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+
+    // Captains rename the team and edit their teammates' blocks, but don't delete the team
+    let (status, _) = send_as(&app, Some(&bob.token), Method::PATCH, &team_uri, rename).await;
+    assert_eq!(status, StatusCode::OK);
+    let block = create_block(&app, &bob, carol.id, "18:00:00", "22:00:00", DAILY).await;
+    let (status, body) = send_as(&app, Some(&bob.token), Method::DELETE, &team_uri, None).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+
+    // Outsiders can't touch the team's blocks
+    let dave = create_player(&app, "dave").await;
+    let (status, body) = send_as(
+        &app,
+        Some(&dave.token),
+        Method::DELETE,
+        &format!("/available-blocks/by-id/{}", block["id"]),
+        None,
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+
+    // Captains remove members, members can leave, nobody removes the owner
+    let (status, _) = send_as(&app, Some(&bob.token), Method::DELETE, &carol_uri, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let alice_uri = format!("/team/by-id/{}/roster/{}", team_id, alice.id);
+    let (status, body) = send_as(&app, Some(&bob.token), Method::DELETE, &alice_uri, None).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+    let (status, body) = send_as(&app, Some(&alice.token), Method::DELETE, &alice_uri, None).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+    let (status, _) = send_as(&app, Some(&bob.token), Method::DELETE, &bob_uri, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn team_invites() {
+    let app = app();
+    let alice = create_player(&app, "alice").await;
+    let bob = create_player(&app, "bob").await;
+    let carol = create_player(&app, "carol").await;
+    let team_id = create_team(&app, &alice, "falcons").await;
+    let invite_uri = format!("/team/by-id/{}/invite", team_id);
+
+    let (status, body) = send_as(&app, Some(&alice.token), Method::GET, &invite_uri, None).await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+
+    let (status, invite) = post_as(&app, &alice.token, &invite_uri, json!({ "max_uses": 1 })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(invite["uses"], 0);
+    let code = invite["code"].as_str().unwrap().to_string();
+
+    // Codes are matched case insensitively
+    let (status, membership) = post_as(
+        &app,
+        &bob.token,
+        &format!("/team/join/{}", code.to_lowercase()),
+        json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", membership);
+    assert_eq!(membership["team_id"], team_id);
+    assert_eq!(membership["role"], "member");
+
+    let (status, body) = post_as(&app, &carol.token, &format!("/team/join/{}", code), json!({})).await;
+    assert_problem(status, &body, StatusCode::CONFLICT, "conflict");
+
+    // Members can't see or rotate the code
+    let (status, body) = send_as(&app, Some(&bob.token), Method::GET, &invite_uri, None).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+
+    // Rotating replaces the code
+    let (status, rotated) = post_as(&app, &alice.token, &invite_uri, json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(rotated["code"], invite["code"]);
+    let new_code = rotated["code"].as_str().unwrap();
+    let (status, body) = post_as(&app, &carol.token, &format!("/team/join/{}", code), json!({})).await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+    let (status, _) = post_as(&app, &carol.token, &format!("/team/join/{}", new_code), json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = post_as(&app, &carol.token, &format!("/team/join/{}", new_code), json!({})).await;
+    assert_problem(status, &body, StatusCode::CONFLICT, "already_exists");
+
+    let (status, body) = post_as(
+        &app,
+        &alice.token,
+        &invite_uri,
+        json!({ "expires_at": "2020-01-01T00:00:00Z" }),
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+
+    let (status, _) = send_as(&app, Some(&alice.token), Method::DELETE, &invite_uri, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let dave = create_player(&app, "dave").await;
+    let (status, body) = post_as(&app, &dave.token, &format!("/team/join/{}", new_code), json!({})).await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+}
+
+#[tokio::test]
+async fn team_calendar_feed() {
+    let app = app();
+    let alice = create_player(&app, "alice").await;
+    let bob = create_player(&app, "bob").await;
+    let carol = create_player(&app, "carol").await;
+    let team_id = create_team(&app, &alice, "falcons").await;
+    join_team(&app, &alice, team_id, bob.id).await;
+    create_block(&app, &alice, alice.id, "18:00:00", "22:00:00", DAILY).await;
+    create_block(&app, &bob, bob.id, "20:00:00", "23:00:00", DAILY).await;
+    let feed_uri = format!("/team/by-id/{}/feed", team_id);
+
+    let (status, body) = send_as(&app, Some(&bob.token), Method::GET, &feed_uri, None).await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+
+    // Only captains create the token, everyone on the roster can read it
+    let (status, body) = post_as(&app, &bob.token, &feed_uri, json!({})).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+    let (status, feed) = post_as(&app, &alice.token, &feed_uri, json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    let token = feed["token"].as_str().unwrap().to_string();
+    let (status, body) = send_as(&app, Some(&bob.token), Method::GET, &feed_uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["token"], token.as_str());
+    let (status, body) = send_as(&app, Some(&carol.token), Method::GET, &feed_uri, None).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+
+    // No session needed, the token is the credential
+    let (status, content_type, body) = get_text(&app, &format!("/feeds/team/{}.ics", token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(content_type.starts_with("text/calendar"));
+    assert!(body.contains("X-WR-CALNAME:falcons availability\r\n"));
+    let starts: Vec<&str> = body.split("\r\n").filter(|line| line.starts_with("DTSTART:")).collect();
+    let ends: Vec<&str> = body.split("\r\n").filter(|line| line.starts_with("DTEND:")).collect();
+    assert!(!starts.is_empty());
+    assert!(starts.iter().all(|line| line.ends_with("T200000Z")), "{:?}", starts);
+    assert!(ends.iter().all(|line| line.ends_with("T220000Z")), "{:?}", ends);
+
+    let (status, _, _) = get_text(&app, &format!("/feeds/team/{}", token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Rotating breaks the old url
+    let (status, rotated) = post_as(&app, &alice.token, &feed_uri, json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(rotated["token"], token.as_str());
+    let (status, _, _) = get_text(&app, &format!("/feeds/team/{}.ics", token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = send_as(&app, Some(&alice.token), Method::DELETE, &feed_uri, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let new_token = rotated["token"].as_str().unwrap();
+    let (status, _, _) = get_text(&app, &format!("/feeds/team/{}.ics", new_token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn player_calendar_feed() {
+    let app = app();
+    let alice = create_player(&app, "alice").await;
+    let bob = create_player(&app, "bob").await;
+    create_block(&app, &alice, alice.id, "18:00:00", "22:00:00", DAILY).await;
+    let feed_uri = format!("/player/{}/feed", alice.id);
+
+    // The download needs a login as the player or a teammate
+    let calendar_uri = format!("/available-blocks/by-player/{}/calendar.ics", alice.id);
+    let (status, _, _) = get_text(&app, &calendar_uri).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _, _) = get_text_as(&app, &bob.token, &calendar_uri).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Only the player sees and changes their token
+    let (status, body) = send_as(&app, Some(&alice.token), Method::GET, &feed_uri, None).await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+    let (status, body) = post_as(&app, &bob.token, &feed_uri, json!({})).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+    let (status, feed) = post_as(&app, &alice.token, &feed_uri, json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    let token = feed["token"].as_str().unwrap().to_string();
+    let (status, body) = send_as(&app, Some(&alice.token), Method::GET, &feed_uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["token"], token.as_str());
+    let (status, body) = send_as(&app, Some(&bob.token), Method::GET, &feed_uri, None).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+
+    // No session needed, the token is the credential
+    let (status, content_type, body) =
+        get_text(&app, &format!("/feeds/player/{}.ics", token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(content_type.starts_with("text/calendar"));
+    assert!(body.contains("X-WR-CALNAME:alice availability\r\n"), "{}", body);
+    assert!(body.contains("DTSTART:20261019T180000Z"), "{}", body);
+
+    // Rotating breaks the old url, deleting breaks the new one
+    let (status, rotated) = post_as(&app, &alice.token, &feed_uri, json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(rotated["token"], token.as_str());
+    let (status, _, _) = get_text(&app, &format!("/feeds/player/{}.ics", token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = send_as(&app, Some(&alice.token), Method::DELETE, &feed_uri, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let new_token = rotated["token"].as_str().unwrap();
+    let (status, _, _) = get_text(&app, &format!("/feeds/player/{}.ics", new_token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn team_events_and_rsvps() {
+    let app = app();
+    let alice = create_player(&app, "alice").await;
+    let bob = create_player(&app, "bob").await;
+    let carol = create_player(&app, "carol").await;
+    let team_id = create_team(&app, &alice, "falcons").await;
+    let other_team_id = create_team(&app, &carol, "hawks").await;
+    join_team(&app, &alice, team_id, bob.id).await;
+    create_block(&app, &alice, alice.id, "18:00:00", "22:00:00", DAILY).await;
+    create_block(&app, &bob, bob.id, "20:00:00", "23:00:00", DAILY).await;
+    let events_uri = format!("/team/by-id/{}/events", team_id);
+    let practice = json!({
+        "title": "Practice",
+        "start": "2026-10-19T19:00:00Z",
+        "end": "2026-10-19T21:00:00Z",
+        "repeats": "DTSTART:20261019T190000Z\nRRULE:FREQ=WEEKLY;COUNT=2",
+        "location": "Court 2",
+    });
+
+    // Bob's blocks start an hour late for both occurrences
+    let (status, event) = post_as(&app, &alice.token, &events_uri, practice.clone()).await;
+    assert_eq!(status, StatusCode::OK, "{}", event);
+    assert_eq!(event["title"], "Practice");
+    assert_eq!(event["created_by"], alice.user_id);
+    assert_eq!(
+        event["unavailable"],
+        json!([{
+            "player_id": bob.id,
+            "name": "bob",
+            "missed": [
+                { "start": "2026-10-19T19:00:00Z", "end": "2026-10-19T21:00:00Z" },
+                { "start": "2026-10-26T19:00:00Z", "end": "2026-10-26T21:00:00Z" },
+            ],
+        }])
+    );
+    let event_uri = format!("{}/{}", events_uri, event["id"]);
+
+    let (status, body) = post_as(&app, &bob.token, &events_uri, practice.clone()).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+    let mut backwards = practice.clone();
+    backwards["end"] = json!("2026-10-19T18:00:00Z");
+    let (status, body) = post_as(&app, &alice.token, &events_uri, backwards).await;
+    assert_problem(status, &body, StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+    let mut shifted = practice.clone();
+    shifted["repeats"] = json!("DTSTART:20261020T190000Z\nRRULE:FREQ=WEEKLY");
+    let (status, body) = post_as(&app, &alice.token, &events_uri, shifted).await;
+    assert_problem(status, &body, StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+
+    // Members read the schedule, outsiders don't
+    let (status, events) = send_as(&app, Some(&bob.token), Method::GET, &events_uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(events.as_array().unwrap().len(), 1);
+    let (status, body) = send_as(&app, Some(&carol.token), Method::GET, &events_uri, None).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+    let (status, body) = send_as(
+        &app,
+        Some(&carol.token),
+        Method::GET,
+        &format!("/team/by-id/{}/events/{}", other_team_id, event["id"]),
+        None,
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+
+    // Answering again replaces the earlier answer
+    let rsvp_uri = format!("{}/rsvp", event_uri);
+    let yes = Some(json!({ "response": "yes" }));
+    let (status, rsvp) = send_as(&app, Some(&bob.token), Method::PUT, &rsvp_uri, yes.clone()).await;
+    assert_eq!(status, StatusCode::OK, "{}", rsvp);
+    assert_eq!(rsvp["player_id"], bob.id);
+    let maybe = Some(json!({ "response": "maybe" }));
+    let (status, _) = send_as(&app, Some(&bob.token), Method::PUT, &rsvp_uri, maybe).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = send_as(&app, Some(&carol.token), Method::PUT, &rsvp_uri, yes).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+    let rsvps_uri = format!("{}/rsvps", event_uri);
+    let (status, rsvps) = send_as(&app, Some(&alice.token), Method::GET, &rsvps_uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(rsvps.as_array().unwrap().len(), 1);
+    assert_eq!(rsvps[0]["response"], "maybe");
+
+    // Moving the event into bob's blocks clears the flag
+    let mut later = practice.clone();
+    later["start"] = json!("2026-10-19T20:00:00Z");
+    later["repeats"] = json!("DTSTART:20261019T200000Z\nRRULE:FREQ=WEEKLY;COUNT=2");
+    let (status, updated) = send_as(&app, Some(&alice.token), Method::PATCH, &event_uri, Some(later)).await;
+    assert_eq!(status, StatusCode::OK, "{}", updated);
+    assert_eq!(updated["unavailable"], json!([]));
+
+    let (status, _) = send_as(&app, Some(&bob.token), Method::DELETE, &rsvp_uri, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = send_as(&app, Some(&alice.token), Method::DELETE, &event_uri, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, body) = send_as(&app, Some(&alice.token), Method::GET, &event_uri, None).await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+}
+
+#[tokio::test]
+async fn health_endpoints() {
+    let app = health_routes(Arc::new(InMemoryAvailablityStore::new()) as DynAvailStore);
+    let (status, _) = send(&app, Method::GET, "/healthz", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, readiness) = send(&app, Method::GET, "/readyz", None).await;
+    assert_eq!(status, StatusCode::OK, "{}", readiness);
+    assert_eq!(readiness["ready"], true);
+
+    let (status, version) = send(&app, Method::GET, "/version", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(version["version"], env!("CARGO_PKG_VERSION"));
+    assert!(version["git_sha"].is_string());
+    assert_eq!(version["migration"], Value::Null);
+}
+
+#[tokio::test]
+async fn request_ids() {
+    let app = telemetry::trace_requests(app());
+    let request = Request::builder().uri("/team/by-id/1").body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let generated = response.headers().get("x-request-id").unwrap().to_str().unwrap();
+    assert_eq!(generated.len(), 36, "{}", generated);
+
+    // A client's id is kept so its bug report can be matched to the logs
+    let request = Request::builder()
+        .uri("/team/by-id/1")
+        .header("x-request-id", "bug-42")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.headers().get("x-request-id").unwrap(), "bug-42");
+}
+
+#[tokio::test]
+async fn prometheus_metrics() {
+    let store = Arc::new(InMemoryAvailablityStore::new()) as DynAvailStore;
+    let app = metrics::track_requests(api_routes(store.clone()));
+    let alice = create_player(&app, "alice").await;
+    let team_id = create_team(&app, &alice, "falcons").await;
+    create_block(&app, &alice, alice.id, "18:00:00", "22:00:00", DAILY).await;
+    let (status, _) = get_as(
+        &app,
+        &alice.token,
+        &format!("/team/by-id/{}/availability", team_id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, content_type, text) = get_text(&health_routes(store), "/metrics").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(content_type.starts_with("text/plain"), "{}", content_type);
+    // Labelled with the template, not the team's id
+    assert!(
+        text.contains(r#"http_requests_total{method="GET",route="/team/by-id/:id/availability",status="200"}"#),
+        "{}",
+        text
+    );
+    assert!(text.contains(r#"http_request_duration_seconds_bucket{method="POST",route="/available-blocks/create""#));
+    assert!(text.contains(r#"available_blocks_created_total{source="api"}"#));
+    assert!(text.contains(r#"team_overlap_computations_total{kind="availability"}"#));
+}
+
+#[tokio::test]
+async fn join_requests() {
+    let app = app();
+    let alice = create_player(&app, "alice").await;
+    let bob = create_player(&app, "bob").await;
+    let carol = create_player(&app, "carol").await;
+    let team_id = create_team(&app, &alice, "falcons").await;
+    let requests_uri = format!("/team/by-id/{}/join-requests", team_id);
+
+    let (status, request) = post_as(
+        &app,
+        &bob.token,
+        &requests_uri,
+        json!({ "message": "I play support" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", request);
+    assert_eq!(request["status"], "pending");
+    assert_eq!(request["message"], "I play support");
+    assert_eq!(request["resolved_at"], Value::Null);
+    let (status, body) = post_as(&app, &bob.token, &requests_uri, json!({})).await;
+    assert_problem(status, &body, StatusCode::CONFLICT, "already_exists");
+    let (_, rejected) = post_as(&app, &carol.token, &requests_uri, json!({})).await;
+
+    // Only captains see and resolve requests
+    let (status, body) = send_as(&app, Some(&bob.token), Method::GET, &requests_uri, None).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+    let (status, pending) = send_as(
+        &app,
+        Some(&alice.token),
+        Method::GET,
+        &format!("{}?status=pending", requests_uri),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(pending.as_array().unwrap().len(), 2);
+
+    let approve_uri = format!("{}/{}", requests_uri, request["id"]);
+    let approve = Some(json!({ "status": "approved" }));
+    let (status, body) = send_as(&app, Some(&bob.token), Method::PATCH, &approve_uri, approve.clone()).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+    let (status, approved) = send_as(&app, Some(&alice.token), Method::PATCH, &approve_uri, approve.clone()).await;
+    assert_eq!(status, StatusCode::OK, "{}", approved);
+    assert_eq!(approved["status"], "approved");
+    assert_eq!(approved["resolved_by"], alice.user_id);
+    let (status, body) = send_as(&app, Some(&alice.token), Method::PATCH, &approve_uri, approve).await;
+    assert_problem(status, &body, StatusCode::CONFLICT, "conflict");
+
+    let (status, body) = send_as(
+        &app,
+        Some(&alice.token),
+        Method::PATCH,
+        &format!("{}/{}", requests_uri, rejected["id"]),
+        Some(json!({ "status": "rejected" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "rejected");
+
+    // Approval put bob on the roster, the rejection left carol off
+    let (_, roster) = get_as(&app, &alice.token, &format!("/team/by-id/{}/roster", team_id)).await;
+    let names: Vec<&str> = roster
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|member| member["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["alice", "bob"]);
+    let (status, body) = post_as(&app, &bob.token, &requests_uri, json!({})).await;
+    assert_problem(status, &body, StatusCode::CONFLICT, "already_exists");
+
+    let (_, all) = send_as(&app, Some(&alice.token), Method::GET, &requests_uri, None).await;
+    assert_eq!(all.as_array().unwrap().len(), 2);
+    let (_, pending) = send_as(
+        &app,
+        Some(&alice.token),
+        Method::GET,
+        &format!("{}?status=pending", requests_uri),
+        None,
+    )
+    .await;
+    assert_eq!(pending, json!([]));
+}
+
+#[tokio::test]
+async fn user_crud() {
+    let app = app();
+    let (id, token) = register(&app, "alice").await;
+
+    let (status, user) = get(&app, &format!("/user/by-id/{}", id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(user["name"], "alice");
+
+    let (status, user) = get(&app, "/user/by-name/alice").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(user["id"], id);
+
+    let (status, user) = send_as(
+        &app,
+        Some(&token),
+        Method::PATCH,
+        &format!("/user/by-id/{}", id),
+        Some(json!({ "id": id, "name": "alicia" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(user["name"], "alicia");
+
+    let (status, _) = send_as(
+        &app,
+        Some(&token),
+        Method::DELETE,
+        &format!("/user/by-id/{}", id),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, body) = get(&app, "/user/by-name/alicia").await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+}
+
+#[tokio::test]
+async fn user_errors() {
+    let store = Arc::new(InMemoryAvailablityStore::new()) as DynAvailStore;
+    let app = api_routes(store.clone());
+    let (admin_id, admin) = register(&app, "root").await;
+    let (_, token) = register(&app, "mallory").await;
+
+    // Users without a password are only made by admins
+    let (status, body) = post(&app, "/user/create", json!({ "name": "alice" })).await;
+    assert_problem(status, &body, StatusCode::UNAUTHORIZED, "unauthorized");
+    let (status, body) = post_as(&app, &token, "/user/create", json!({ "name": "alice" })).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+
+    store.set_admin(admin_id as i32, true).await.unwrap();
+    let (status, body) = post_as(&app, &admin, "/user/create", json!({ "name": "alice" })).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, body) = post_as(&app, &admin, "/user/create", json!({ "name": "alice" })).await;
+    assert_problem(status, &body, StatusCode::CONFLICT, "already_exists");
+
+    let (status, body) = post_as(
+        &app,
+        &admin,
+        "/user/create",
+        json!({ "name": "a name that is far too long" }),
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+
+    let (status, body) = get(&app, "/user/by-id/42").await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+
+    // Still referenced by a player
+    let bob = create_player(&app, "bob").await;
+    let (status, body) = send_as(
+        &app,
+        Some(&bob.token),
+        Method::DELETE,
+        &format!("/user/by-id/{}", bob.user_id),
+        None,
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::CONFLICT, "conflict");
+}
+
+#[tokio::test]
+async fn auth_sessions() {
+    let app = app();
+    let (id, token) = register(&app, "alice").await;
+
+    let (status, user) = send_as(&app, Some(&token), Method::GET, "/auth/me", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(user["id"], id);
+
+    // The session cookie works as well as the bearer token
+    let request = Request::builder()
+        .uri("/auth/me")
+        .header(header::COOKIE, format!("theme=dark; session={}", token))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The cookie is only ever sent over https
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri("/auth/login")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(json!({ "name": "alice", "password": "correct horse" }).to_string()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+    assert!(cookie.starts_with("session="), "{}", cookie);
+    assert!(cookie.contains("; HttpOnly; Secure;"), "{}", cookie);
+
+    let (status, body) = post(
+        &app,
+        "/auth/login",
+        json!({ "name": "alice", "password": "wrong password" }),
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::UNAUTHORIZED, "unauthorized");
+    let (status, body) = post(
+        &app,
+        "/auth/login",
+        json!({ "name": "nobody", "password": "correct horse" }),
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::UNAUTHORIZED, "unauthorized");
+
+    let (status, session) = post(
+        &app,
+        "/auth/login",
+        json!({ "name": "alice", "password": "correct horse" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let second = session["token"].as_str().unwrap().to_string();
+    assert_ne!(second, token);
+
+    let (status, _) = send_as(&app, Some(&token), Method::POST, "/auth/logout", None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, body) = send_as(&app, Some(&token), Method::GET, "/auth/me", None).await;
+    assert_problem(status, &body, StatusCode::UNAUTHORIZED, "unauthorized");
+    // Logging out ends only that session
+    let (status, _) = send_as(&app, Some(&second), Method::GET, "/auth/me", None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn auth_errors() {
+    let app = app();
+    let (status, body) = post(
+        &app,
+        "/auth/register",
+        json!({ "name": "alice", "password": "short" }),
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+
+    // A line break in a name would end the line it is exported on
+    let (status, body) = post(
+        &app,
+        "/auth/register",
+        json!({ "name": "eve\r\nBEGIN:VEVENT", "password": "correct horse" }),
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+
+    let (alice, _) = register(&app, "alice").await;
+    let (status, body) = post(
+        &app,
+        "/auth/register",
+        json!({ "name": "alice", "password": "another password" }),
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::CONFLICT, "already_exists");
+
+    let (status, body) = send(&app, Method::DELETE, &format!("/user/by-id/{}", alice), None).await;
+    assert_problem(status, &body, StatusCode::UNAUTHORIZED, "unauthorized");
+
+    let (_, bob_token) = register(&app, "bob").await;
+    let (status, body) = send_as(
+        &app,
+        Some(&bob_token),
+        Method::DELETE,
+        &format!("/user/by-id/{}", alice),
+        None,
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+}
+
+#[tokio::test]
+async fn player_crud() {
+    let app = app();
+    let alice = create_player(&app, "alice").await;
+
+    let (status, player) = get(&app, &format!("/player/{}", alice.id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(player["user_id"], alice.user_id);
+
+    let (status, player) = get(&app, &format!("/player/by-user-id/{}", alice.user_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(player["id"], alice.id);
+
+    let (status, player) = send_as(
+        &app,
+        Some(&alice.token),
+        Method::PATCH,
+        &format!("/player/{}", alice.id),
+        Some(json!({ "id": alice.id, "user_id": alice.user_id })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(player["user_id"], alice.user_id);
+
+    let (status, _) = send_as(
+        &app,
+        Some(&alice.token),
+        Method::DELETE,
+        &format!("/player/{}", alice.id),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, body) = get(&app, &format!("/player/{}", alice.id)).await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+
+    // Deleting the player must not delete its user
+    let (status, _) = get(&app, &format!("/user/by-id/{}", alice.user_id)).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn player_errors() {
+    let app = app();
+    let (_, token) = register(&app, "alice").await;
+    let (status, body) = post_as(&app, &token, "/player/create", json!({ "user_id": 7 })).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+
+    // Players can't be handed to another user
+    let bob = create_player(&app, "bob").await;
+    let (status, body) = send_as(
+        &app,
+        Some(&bob.token),
+        Method::PATCH,
+        &format!("/player/{}", bob.id),
+        Some(json!({ "id": bob.id, "user_id": 1 })),
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+    let (status, body) = send_as(&app, Some(&token), Method::DELETE, &format!("/player/{}", bob.id), None).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+
+    let (status, body) = get(&app, "/player/by-user-id/7").await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+
+    let (status, body) = get(&app, "/player/7").await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+
+    let (status, body) = get_as(&app, &token, "/player/7/teams").await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+}
+
+#[tokio::test]
+async fn roster_membership() {
+    let app = app();
+    let alice = create_player(&app, "alice").await;
+    let bob = create_player(&app, "bob").await;
+    let team_id = create_team(&app, &alice, "falcons").await;
+    join_team(&app, &alice, team_id, bob.id).await;
+
+    let (status, roster) = get_as(
+        &app,
+        &alice.token,
+        &format!("/team/by-id/{}/roster", team_id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let names: Vec<&str> = roster
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|member| member["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["alice", "bob"]);
+
+    let (status, teams) = get_as(&app, &alice.token, &format!("/player/{}/teams", bob.id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(teams[0]["name"], "falcons");
+
+    let bob_uri = format!("/team/by-id/{}/roster/{}", team_id, bob.id);
+    let (status, body) = send_as(&app, Some(&alice.token), Method::PUT, &bob_uri, None).await;
+    assert_problem(status, &body, StatusCode::CONFLICT, "already_exists");
+
+    let (status, _) = send_as(&app, Some(&alice.token), Method::DELETE, &bob_uri, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (_, roster) = get_as(&app, &alice.token, &format!("/team/by-id/{}/roster", team_id)).await;
+    assert_eq!(roster.as_array().unwrap().len(), 1);
+
+    let (status, body) = send_as(&app, Some(&alice.token), Method::DELETE, &bob_uri, None).await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+}
+
+#[tokio::test]
+async fn roster_errors() {
+    let app = app();
+    let alice = create_player(&app, "alice").await;
+    let team_id = create_team(&app, &alice, "falcons").await;
+
+    let (status, body) = get_as(&app, &alice.token, "/team/by-id/99/roster").await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+
+    // Only members read the roster
+    let roster_uri = format!("/team/by-id/{}/roster", team_id);
+    let (status, body) = get(&app, &roster_uri).await;
+    assert_problem(status, &body, StatusCode::UNAUTHORIZED, "unauthorized");
+    let outsider = create_player(&app, "mallory").await;
+    let (status, body) = get_as(&app, &outsider.token, &roster_uri).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+
+    let (status, body) = send_as(
+        &app,
+        Some(&alice.token),
+        Method::PUT,
+        &format!("/team/by-id/99/roster/{}", alice.id),
+        None,
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+
+    let (status, body) = send_as(
+        &app,
+        Some(&alice.token),
+        Method::PUT,
+        &format!("/team/by-id/{}/roster/99", team_id),
+        None,
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+
+    let (status, body) = send(
+        &app,
+        Method::PUT,
+        &format!("/team/by-id/{}/roster/{}", team_id, alice.id),
+        None,
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::UNAUTHORIZED, "unauthorized");
+}
+
+#[tokio::test]
+async fn available_block_crud() {
+    let app = app();
+    let alice = create_player(&app, "alice").await;
+    let player_id = alice.id;
+    let block = create_block(&app, &alice, player_id, "18:00:00", "22:00:00", DAILY).await;
+    let id = block["id"].as_i64().unwrap();
+    assert_eq!(block["timezone"], "UTC");
+
+    let (status, fetched) =
+        get_as(&app, &alice.token, &format!("/available-blocks/by-id/{}", id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(fetched["startTime"], "18:00:00");
+    assert_eq!(fetched["playerId"], player_id);
+
+    let (status, blocks) = get_as(
+        &app,
+        &alice.token,
+        &format!("/available-blocks/by-player/{}", player_id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(blocks.as_array().unwrap().len(), 1);
+
+    let mut updated = fetched.clone();
+    updated["endTime"] = json!("23:00:00");
+    let (status, updated) = send_as(
+        &app,
+        Some(&alice.token),
+        Method::PATCH,
+        &format!("/available-blocks/by-id/{}", id),
+        Some(updated),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(updated["endTime"], "23:00:00");
+
+    let (status, _) = send_as(
+        &app,
+        Some(&alice.token),
+        Method::DELETE,
+        &format!("/available-blocks/by-id/{}", id),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, body) =
+        get_as(&app, &alice.token, &format!("/available-blocks/by-id/{}", id)).await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+
+    let (status, blocks) = get_as(
+        &app,
+        &alice.token,
+        &format!("/available-blocks/by-player/{}", player_id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(blocks, json!([]));
+}
+
+#[tokio::test]
+async fn available_block_errors() {
+    let app = app();
+    let alice = create_player(&app, "alice").await;
+    let player_id = alice.id;
+    let block = json!({
+        "startTime": "18:00:00",
+        "endTime": "22:00:00",
+        "needWarning": false,
+        "repeats": "not a rule",
+        "playerId": player_id,
+    });
+    let (status, body) = post_as(&app, &alice.token, "/available-blocks/create", block.clone()).await;
+    assert_problem(status, &body, StatusCode::UNPROCESSABLE_ENTITY, "invalid_body");
+
+    let mut same_times = block.clone();
+    same_times["repeats"] = json!(DAILY);
+    same_times["endTime"] = json!("18:00:00");
+    let (status, body) = post_as(&app, &alice.token, "/available-blocks/create", same_times).await;
+    assert_problem(status, &body, StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+
+    let mut unknown_player = block.clone();
+    unknown_player["repeats"] = json!(DAILY);
+    unknown_player["playerId"] = json!(99);
+    let (status, body) = post_as(&app, &alice.token, "/available-blocks/create", unknown_player).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+
+    let mut bad_timezone = block.clone();
+    bad_timezone["repeats"] = json!(DAILY);
+    bad_timezone["timezone"] = json!("Mars/Olympus");
+    let (status, body) = post_as(&app, &alice.token, "/available-blocks/create", bad_timezone).await;
+    assert_problem(status, &body, StatusCode::UNPROCESSABLE_ENTITY, "invalid_body");
+
+    let (status, body) = get_as(&app, &alice.token, "/available-blocks/by-id/99").await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+
+    let (status, body) = get_as(
+        &app,
+        &alice.token,
+        &format!("/available-blocks/by-player/{}?on_invalid=ignore", player_id),
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::BAD_REQUEST, "invalid_query");
+
+    // Blocks and teams are shown to the player and their teammates only
+    let block = create_block(&app, &alice, player_id, "18:00:00", "22:00:00", DAILY).await;
+    let uris = [
+        format!("/available-blocks/by-player/{}", player_id),
+        format!("/available-blocks/by-id/{}", block["id"]),
+        format!("/available-blocks/by-id/{}/occurrences", block["id"]),
+        format!("/player/{}/teams", player_id),
+    ];
+    let bob = create_player(&app, "bob").await;
+    for uri in &uris {
+        let (status, body) = get(&app, uri).await;
+        assert_problem(status, &body, StatusCode::UNAUTHORIZED, "unauthorized");
+        let (status, body) = get_as(&app, &bob.token, uri).await;
+        assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+    }
+    let team_id = create_team(&app, &alice, "falcons").await;
+    join_team(&app, &alice, team_id, bob.id).await;
+    for uri in &uris {
+        let (status, body) = get_as(&app, &bob.token, uri).await;
+        assert_eq!(status, StatusCode::OK, "{}: {}", uri, body);
+    }
+    let (status, body) = get_as(&app, &bob.token, "/available-blocks/by-player/99").await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+}
+
+#[tokio::test]
+async fn available_block_occurrences() {
+    let app = app();
+    let alice = create_player(&app, "alice").await;
+    let block = create_block(&app, &alice, alice.id, "18:00:00", "22:00:00", DAILY).await;
+
+    let (status, body) = get_as(
+        &app,
+        &alice.token,
+        &format!(
+            "/available-blocks/by-id/{}/occurrences?from=2026-10-19T00:00:00Z&to=2026-10-22T00:00:00Z",
+            block["id"]
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["limited"], false);
+    assert_eq!(
+        body["occurrences"],
+        json!([
+            { "start": "2026-10-19T18:00:00Z", "end": "2026-10-19T22:00:00Z" },
+            { "start": "2026-10-20T18:00:00Z", "end": "2026-10-20T22:00:00Z" },
+            { "start": "2026-10-21T18:00:00Z", "end": "2026-10-21T22:00:00Z" },
+        ])
+    );
+
+    let (status, body) = get_as(
+        &app,
+        &alice.token,
+        &format!(
+            "/available-blocks/by-id/{}/occurrences?from=2026-10-19T00:00:00Z&limit=2",
+            block["id"]
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["occurrences"].as_array().unwrap().len(), 2);
+    assert_eq!(body["limited"], true);
+
+    let (status, body) = get_as(&app, &alice.token, "/available-blocks/by-id/99/occurrences").await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+
+    let (status, body) = get_as(
+        &app,
+        &alice.token,
+        &format!(
+            "/available-blocks/by-id/{}/occurrences?from=2026-10-19T00:00:00Z&to=2026-10-18T00:00:00Z",
+            block["id"]
+        ),
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+}
+
+#[tokio::test]
+async fn available_blocks_calendar_export() {
+    let app = app();
+    let alice = create_player(&app, "alice").await;
+    let (status, block) = post_as(
+        &app,
+        &alice.token,
+        "/available-blocks/create",
+        json!({
+            "startTime": "22:00:00",
+            "endTime": "01:00:00",
+            "needWarning": true,
+            "repeats": "DTSTART:20261019T000000Z\nRRULE:FREQ=DAILY;UNTIL=20261031T000000Z\nEXDATE:20261021T000000Z",
+            "playerId": alice.id,
+            "timezone": "Europe/Berlin",
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", block);
+
+    let (status, content_type, body) = get_text_as(
+        &app,
+        &alice.token,
+        &format!("/available-blocks/by-player/{}/calendar.ics", alice.id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(content_type.starts_with("text/calendar"));
+    assert!(body.starts_with("BEGIN:VCALENDAR\r\n"));
+    assert!(body.ends_with("END:VCALENDAR\r\n"));
+    let lines: Vec<&str> = body.split("\r\n").collect();
+    assert!(lines.contains(&"BEGIN:VEVENT"));
+    assert!(lines.contains(&format!("UID:available-block-{}@team-availability", block["id"]).as_str()));
+    assert!(lines.contains(&"DTSTART;TZID=Europe/Berlin:20261019T220000"));
+    // Overnight blocks end the next day
+    assert!(lines.contains(&"DTEND;TZID=Europe/Berlin:20261020T010000"));
+    // UNTIL moves to the last occurrence's start, 22:00 CET
+    let rule = lines
+        .iter()
+        .skip_while(|line| **line != "BEGIN:VEVENT")
+        .find(|line| line.starts_with("RRULE:"))
+        .unwrap();
+    assert!(rule.contains("FREQ=DAILY"), "{}", rule);
+    assert!(rule.contains("UNTIL=20261031T210000Z"), "{}", rule);
+    assert!(!rule.contains("BYHOUR"), "{}", rule);
+    assert!(lines.contains(&"EXDATE;TZID=Europe/Berlin:20261021T220000"));
+    assert!(lines.contains(&"DESCRIPTION:Ask before scheduling"));
+    assert!(body.lines().all(|line| line.trim_end_matches('\r').len() <= 75));
+
+    // The TZID is defined before the events, with yearly rules from the year before
+    let timezone: Vec<&str> = lines
+        .iter()
+        .skip_while(|line| **line != "BEGIN:VTIMEZONE")
+        .take_while(|line| **line != "END:VTIMEZONE")
+        .copied()
+        .collect();
+    assert_eq!(
+        timezone,
+        vec![
+            "BEGIN:VTIMEZONE",
+            "TZID:Europe/Berlin",
+            "BEGIN:DAYLIGHT",
+            "DTSTART:20250330T020000",
+            "RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU",
+            "TZOFFSETFROM:+0100",
+            "TZOFFSETTO:+0200",
+            "TZNAME:CEST",
+            "END:DAYLIGHT",
+            "BEGIN:STANDARD",
+            "DTSTART:20251026T030000",
+            "RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU",
+            "TZOFFSETFROM:+0200",
+            "TZOFFSETTO:+0100",
+            "TZNAME:CET",
+            "END:STANDARD",
+        ]
+    );
+    let position = |wanted: &str| lines.iter().position(|line| *line == wanted).unwrap();
+    assert!(position("END:VTIMEZONE") < position("BEGIN:VEVENT"));
+    assert_eq!(body.matches("BEGIN:VTIMEZONE").count(), 1);
+
+    let (status, _, _) =
+        get_text_as(&app, &alice.token, "/available-blocks/by-player/99/calendar.ics").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+// A rule's DTSTART needn't match the rule, the export starts on the first real occurrence
+#[tokio::test]
+async fn calendar_export_starts_on_the_first_occurrence() {
+    let app = app();
+    let alice = create_player(&app, "alice").await;
+    create_block(
+        &app,
+        &alice,
+        alice.id,
+        "18:00:00",
+        "20:00:00",
+        "DTSTART:20261019T000000Z\nRRULE:FREQ=WEEKLY;BYDAY=TU;COUNT=2",
+    )
+    .await;
+
+    let (status, _, body) = get_text_as(
+        &app,
+        &alice.token,
+        &format!("/available-blocks/by-player/{}/calendar.ics", alice.id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let lines: Vec<&str> = body.split("\r\n").collect();
+    assert!(lines.contains(&"DTSTART:20261020T180000Z"), "{}", body);
+    assert!(lines.contains(&"DTEND:20261020T200000Z"), "{}", body);
+    let rule = lines.iter().find(|line| line.starts_with("RRULE:")).unwrap();
+    assert!(rule.contains("COUNT=2") && rule.contains("BYDAY=TU"), "{}", rule);
+    // UTC times need no VTIMEZONE
+    assert!(!body.contains("BEGIN:VTIMEZONE"));
+}
+
+#[tokio::test]
+async fn freebusy_exports() {
+    let app = app();
+    let alice = create_player(&app, "alice").await;
+    let bob = create_player(&app, "bob").await;
+    let team_id = create_team(&app, &alice, "falcons").await;
+    join_team(&app, &alice, team_id, bob.id).await;
+    create_block(&app, &alice, alice.id, "18:00:00", "22:00:00", DAILY).await;
+    create_block(&app, &alice, alice.id, "21:00:00", "23:00:00", DAILY).await;
+    create_block(&app, &bob, bob.id, "20:00:00", "23:30:00", DAILY).await;
+    let window = "from=2026-10-19T00:00:00Z&to=2026-10-20T00:00:00Z";
+
+    // A player's free/busy is shown to the player and their teammates
+    let player_uri = format!("/available-blocks/by-player/{}/freebusy.ics?{}", alice.id, window);
+    let (status, _, _) = get_text(&app, &player_uri).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let carol = create_player(&app, "carol").await;
+    let (status, _, _) = get_text_as(&app, &carol.token, &player_uri).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, content_type, body) = get_text_as(&app, &bob.token, &player_uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(content_type.starts_with("text/calendar"));
+    let lines: Vec<&str> = body.split("\r\n").collect();
+    assert!(lines.contains(&"BEGIN:VFREEBUSY"));
+    assert!(lines.contains(&"DTSTART:20261019T000000Z"));
+    assert!(lines.contains(&"DTEND:20261020T000000Z"));
+    // Overlapping blocks are joined
+    let periods: Vec<&str> = lines.iter().copied().filter(|line| line.starts_with("FREEBUSY")).collect();
+    assert_eq!(
+        periods,
+        [
+            "FREEBUSY;FBTYPE=FREE:20261019T180000Z/20261019T230000Z",
+            "FREEBUSY;FBTYPE=BUSY:20261019T000000Z/20261019T180000Z",
+            "FREEBUSY;FBTYPE=BUSY:20261019T230000Z/20261020T000000Z",
+        ]
+    );
+
+    let team_uri = format!("/team/by-id/{}/freebusy.ics?{}", team_id, window);
+    let (status, _, _) = get_text(&app, &team_uri).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _, _) = get_text_as(&app, &carol.token, &team_uri).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _, body) = get_text_as(&app, &bob.token, &team_uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.matches("BEGIN:VFREEBUSY").count(), 2);
+    assert!(body.contains(&format!("ATTENDEE;CN=\"bob\":urn:team-availability:player:{}", bob.id)));
+
+    let (status, _, body) = get_text_as(
+        &app,
+        &bob.token,
+        &format!("{}&aggregate=true", team_uri),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.matches("BEGIN:VFREEBUSY").count(), 1);
+    assert!(body.contains("FREEBUSY;FBTYPE=FREE:20261019T200000Z/20261019T230000Z\r\n"));
+
+    let (status, _, _) = get_text_as(&app, &bob.token, "/team/by-id/99/freebusy.ics").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+const IMPORTED_EVENTS: &str = "BEGIN:VCALENDAR
+VERSION:2.0
+BEGIN:VEVENT
+UID:practice
+SUMMARY:Practice\\, weekly
+DTSTART;TZID=Europe/Berlin:20261019T190000
+DTEND;TZID=Europe/Berlin:20261019T210000
+RRULE:FREQ=WEEKLY;BYDAY=MO,WE;UNTIL=20261130T180000Z
+EXDATE;TZID=Europe/Berlin:20261021T190000
+BEGIN:VALARM
+TRIGGER:-PT15M
+END:VALARM
+END:VEVENT
+BEGIN:VEVENT
+UID:scrim
+DTSTART:20261024T150000Z
+DURATION:PT3H
+END:VEVENT
+BEGIN:VEVENT
+UID:holiday
+DTSTART;VALUE=DATE:20261026
+END:VEVENT
+END:VCALENDAR
+";
+
+#[tokio::test]
+async fn import_available_blocks_from_ics() {
+    let app = app();
+    let alice = create_player(&app, "alice").await;
+    let bob = create_player(&app, "bob").await;
+    let uri = format!("/available-blocks/import?player_id={}&dry_run=true", alice.id);
+
+    let (status, body) = post_ics(&app, &alice.token, &uri, IMPORTED_EVENTS).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["dryRun"], true);
+    let blocks = body["blocks"].as_array().unwrap();
+    assert_eq!(blocks.len(), 2);
+    assert_eq!(blocks[0]["startTime"], "19:00:00");
+    assert_eq!(blocks[0]["endTime"], "21:00:00");
+    assert_eq!(blocks[0]["timezone"], "Europe/Berlin");
+    let repeats = blocks[0]["repeats"].as_str().unwrap();
+    assert!(repeats.contains("BYDAY=MO,WE"), "{}", repeats);
+    assert!(repeats.contains("UNTIL=20261130T235959Z"), "{}", repeats);
+    assert!(repeats.contains("EXDATE;VALUE=DATE-TIME:20261021T000000Z"), "{}", repeats);
+    assert_eq!(blocks[1]["startTime"], "15:00:00");
+    assert_eq!(blocks[1]["endTime"], "18:00:00");
+    assert_eq!(body["skipped"][0]["uid"], "holiday");
+
+    // Nothing is written by a dry run
+    let (_, stored) = get_as(
+        &app,
+        &alice.token,
+        &format!("/available-blocks/by-player/{}", alice.id),
+    )
+    .await;
+    assert_eq!(stored, json!([]));
+
+    // The weekly block keeps its first Wednesday off
+    let (status, occurrences) = post(
+        &app,
+        "/available-blocks/preview?from=2026-10-19T00:00:00Z&to=2026-10-27T00:00:00Z",
+        blocks[0].clone(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        occurrences["occurrences"],
+        json!([
+            { "start": "2026-10-19T17:00:00Z", "end": "2026-10-19T19:00:00Z" },
+            { "start": "2026-10-26T18:00:00Z", "end": "2026-10-26T20:00:00Z" },
+        ])
+    );
+
+    let uri = format!("/available-blocks/import?player_id={}", alice.id);
+    let (status, body) = post_ics(&app, &alice.token, &uri, IMPORTED_EVENTS).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["dryRun"], false);
+    assert!(body["blocks"][0]["id"].is_number());
+    let (_, stored) = get_as(
+        &app,
+        &alice.token,
+        &format!("/available-blocks/by-player/{}", alice.id),
+    )
+    .await;
+    assert_eq!(stored.as_array().unwrap().len(), 2);
+
+    let (status, body) = post_ics(&app, &bob.token, &uri, IMPORTED_EVENTS).await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+    let (status, body) = post_ics(&app, &alice.token, &uri, "not a calendar").await;
+    assert_problem(status, &body, StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+}
+
+#[tokio::test]
+async fn import_busy_time_as_free_blocks() {
+    let app = app();
+    let alice = create_player(&app, "alice").await;
+    let busy = "BEGIN:VCALENDAR
+BEGIN:VEVENT
+UID:lunch
+DTSTART:20261019T120000Z
+DTEND:20261019T130000Z
+RRULE:FREQ=DAILY
+END:VEVENT
+BEGIN:VEVENT
+UID:dentist
+DTSTART:20261020T180000Z
+DTEND:20261020T190000Z
+END:VEVENT
+BEGIN:VEVENT
+UID:reminder
+DTSTART:20261020T090000Z
+DTEND:20261020T100000Z
+TRANSP:TRANSPARENT
+END:VEVENT
+END:VCALENDAR
+";
+    let (status, body) = post_ics(
+        &app,
+        &alice.token,
+        &format!(
+            "/available-blocks/import?player_id={}&mode=busy&dry_run=true&wake_start=08:00&wake_end=22:00&from=2026-10-19T00:00:00Z&to=2026-10-21T00:00:00Z",
+            alice.id
+        ),
+        busy,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let windows: Vec<(&str, &str)> = body["blocks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|block| {
+            (
+                block["startTime"].as_str().unwrap(),
+                block["endTime"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        windows,
+        [
+            ("08:00:00", "12:00:00"),
+            ("13:00:00", "22:00:00"),
+            ("13:00:00", "18:00:00"),
+            ("19:00:00", "22:00:00"),
+        ]
+    );
+    // Windows with the same times on several dates share one block
+    let repeats = body["blocks"][0]["repeats"].as_str().unwrap();
+    assert!(repeats.contains("20261019T000000Z,20261020T000000Z"), "{}", repeats);
+    assert_eq!(body["skipped"][0]["uid"], "reminder");
+}
+
+#[tokio::test]
+async fn preview_handles_timezones_and_overnight_blocks() {
+    let app = app();
+    // Berlin leaves DST on 2026-10-25, 22:00 local moves from 20:00 to 21:00 UTC
+    let (status, body) = post(
+        &app,
+        "/available-blocks/preview?from=2026-10-24T00:00:00Z&to=2026-10-26T00:00:00Z",
+        json!({
+            "startTime": "22:00:00",
+            "endTime": "01:00:00",
+            "needWarning": false,
+            "repeats": DAILY,
+            "playerId": 1,
+            "timezone": "Europe/Berlin",
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(
+        body["occurrences"],
+        json!([
+            { "start": "2026-10-24T20:00:00Z", "end": "2026-10-24T23:00:00Z" },
+            { "start": "2026-10-25T21:00:00Z", "end": "2026-10-26T00:00:00Z" },
+        ])
+    );
+}
+
+#[tokio::test]
+async fn team_availability_and_suggestions() {
+    let app = app();
+    let alice = create_player(&app, "alice").await;
+    let bob = create_player(&app, "bob").await;
+    let team_id = create_team(&app, &alice, "falcons").await;
+    join_team(&app, &alice, team_id, bob.id).await;
+    create_block(&app, &alice, alice.id, "18:00:00", "22:00:00", DAILY).await;
+    create_block(&app, &bob, bob.id, "20:00:00", "23:00:00", DAILY).await;
+
+    let (status, body) = get_as(
+        &app,
+        &bob.token,
+        &format!(
+            "/team/by-id/{}/availability?from=2026-10-19T00:00:00Z&to=2026-10-20T00:00:00Z",
+            team_id
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["intervals"],
+        json!([
+            { "start": "2026-10-19T18:00:00Z", "end": "2026-10-19T20:00:00Z", "playerIds": [alice.id] },
+            { "start": "2026-10-19T20:00:00Z", "end": "2026-10-19T22:00:00Z", "playerIds": [alice.id, bob.id] },
+            { "start": "2026-10-19T22:00:00Z", "end": "2026-10-19T23:00:00Z", "playerIds": [bob.id] },
+        ])
+    );
+    assert_eq!(
+        body["allAvailable"],
+        json!([{ "start": "2026-10-19T20:00:00Z", "end": "2026-10-19T22:00:00Z" }])
+    );
+    // Nested roster members follow the body's casing
+    assert_eq!(body["players"][0]["playerId"], alice.id);
+    assert_eq!(body["players"][0]["userId"], alice.user_id);
+
+    let (status, body) = get_as(
+        &app,
+        &bob.token,
+        &format!(
+            "/team/by-id/{}/suggestions?from=2026-10-19T00:00:00Z&to=2026-10-20T00:00:00Z&min_players=1&min_duration=180&limit=2",
+            team_id
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let suggestions = body.as_array().unwrap();
+    assert_eq!(suggestions.len(), 2);
+    assert_eq!(suggestions[0]["start"], "2026-10-19T18:00:00Z");
+    assert_eq!(suggestions[0]["durationMinutes"], 240);
+    assert_eq!(suggestions[0]["missingPlayers"][0]["name"], "bob");
+    assert_eq!(suggestions[0]["missingPlayers"][0]["playerId"], bob.id);
+    assert_eq!(suggestions[1]["durationMinutes"], 180);
+
+    let (status, body) = get_as(&app, &bob.token, "/team/by-id/99/availability").await;
+    assert_problem(status, &body, StatusCode::NOT_FOUND, "not_found");
+
+    let (status, body) = get_as(
+        &app,
+        &bob.token,
+        &format!("/team/by-id/{}/suggestions?min_players=3", team_id),
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+
+    // Outsiders see neither
+    let carol = create_player(&app, "carol").await;
+    for path in ["availability", "suggestions"] {
+        let (status, body) = get_as(
+            &app,
+            &carol.token,
+            &format!("/team/by-id/{}/{}", team_id, path),
+        )
+        .await;
+        assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+    }
+}
+
+#[tokio::test]
+async fn admin_reports_no_invalid_blocks() {
+    let store = Arc::new(InMemoryAvailablityStore::new()) as DynAvailStore;
+    let app = api_routes(store.clone());
+    let alice = create_player(&app, "alice").await;
+    create_block(&app, &alice, alice.id, "18:00:00", "22:00:00", DAILY).await;
+
+    let (status, body) = get(&app, "/admin/available-blocks/invalid").await;
+    assert_problem(status, &body, StatusCode::UNAUTHORIZED, "unauthorized");
+    let (status, body) = send_as(
+        &app,
+        Some(&alice.token),
+        Method::GET,
+        "/admin/available-blocks/invalid",
+        None,
+    )
+    .await;
+    assert_problem(status, &body, StatusCode::FORBIDDEN, "forbidden");
+
+    store.set_admin(alice.user_id as i32, true).await.unwrap();
+    let (status, body) = send_as(
+        &app,
+        Some(&alice.token),
+        Method::GET,
+        "/admin/available-blocks/invalid",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!([]));
+}
@@ -0,0 +1,190 @@
+// Helpers shared by the API tests, not every test file uses all of them
+#![allow(dead_code)]
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+pub const DAILY: &str = "DTSTART:20261019T000000Z\nRRULE:FREQ=DAILY";
+
+pub async fn send(app: &Router, method: Method, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+    send_as(app, None, method, uri, body).await
+}
+
+// Sends the request with a bearer token when one is given
+pub async fn send_as(
+    app: &Router,
+    token: Option<&str>,
+    method: Method,
+    uri: &str,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let mut builder = Request::builder().method(method).uri(uri);
+    if let Some(token) = token {
+        builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", token));
+    }
+    let request = match body {
+        Some(body) => builder
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string())),
+        None => builder.body(Body::empty()),
+    }
+    .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let value = if bytes.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(&bytes).unwrap()
+    };
+    (status, value)
+}
+
+pub async fn get(app: &Router, uri: &str) -> (StatusCode, Value) {
+    send(app, Method::GET, uri, None).await
+}
+
+pub async fn get_as(app: &Router, token: &str, uri: &str) -> (StatusCode, Value) {
+    send_as(app, Some(token), Method::GET, uri, None).await
+}
+
+// For the endpoints that don't answer with JSON, returns the content type with the body
+pub async fn get_text(app: &Router, uri: &str) -> (StatusCode, String, String) {
+    get_text_with(app, None, uri).await
+}
+
+pub async fn get_text_as(app: &Router, token: &str, uri: &str) -> (StatusCode, String, String) {
+    get_text_with(app, Some(token), uri).await
+}
+
+async fn get_text_with(
+    app: &Router,
+    token: Option<&str>,
+    uri: &str,
+) -> (StatusCode, String, String) {
+    let mut builder = Request::builder().uri(uri);
+    if let Some(token) = token {
+        builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", token));
+    }
+    let response = app.clone().oneshot(builder.body(Body::empty()).unwrap()).await.unwrap();
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .map(|value| value.to_str().unwrap().to_string())
+        .unwrap_or_default();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    (status, content_type, String::from_utf8(bytes.to_vec()).unwrap())
+}
+
+pub async fn post(app: &Router, uri: &str, body: Value) -> (StatusCode, Value) {
+    send(app, Method::POST, uri, Some(body)).await
+}
+
+// Registers a user, returns the user id and session token
+pub async fn register(app: &Router, name: &str) -> (i64, String) {
+    let (status, session) = post(
+        app,
+        "/auth/register",
+        json!({ "name": name, "password": "correct horse" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", session);
+    (
+        session["user"]["id"].as_i64().unwrap(),
+        session["token"].as_str().unwrap().to_string(),
+    )
+}
+
+// A registered user with a player, acting with their own session
+pub struct TestPlayer {
+    pub id: i64,
+    pub user_id: i64,
+    pub token: String,
+}
+
+pub async fn post_as(app: &Router, token: &str, uri: &str, body: Value) -> (StatusCode, Value) {
+    send_as(app, Some(token), Method::POST, uri, Some(body)).await
+}
+
+pub async fn create_player(app: &Router, name: &str) -> TestPlayer {
+    let (user_id, token) = register(app, name).await;
+    let (status, player) = post_as(app, &token, "/player/create", json!({ "user_id": user_id })).await;
+    assert_eq!(status, StatusCode::OK, "{}", player);
+    TestPlayer {
+        id: player["id"].as_i64().unwrap(),
+        user_id,
+        token,
+    }
+}
+
+// The team is created by and owned by `owner`
+pub async fn create_team(app: &Router, owner: &TestPlayer, name: &str) -> i64 {
+    let (status, team) = post_as(app, &owner.token, "/team/create", json!({ "name": name })).await;
+    assert_eq!(status, StatusCode::OK, "{}", team);
+    team["id"].as_i64().unwrap()
+}
+
+pub async fn create_block(
+    app: &Router,
+    editor: &TestPlayer,
+    player_id: i64,
+    start: &str,
+    end: &str,
+    repeats: &str,
+) -> Value {
+    let (status, block) = post_as(
+        app,
+        &editor.token,
+        "/available-blocks/create",
+        json!({
+            "startTime": start,
+            "endTime": end,
+            "needWarning": false,
+            "repeats": repeats,
+            "playerId": player_id,
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", block);
+    block
+}
+
+pub async fn join_team(app: &Router, captain: &TestPlayer, team_id: i64, player_id: i64) {
+    let (status, body) = send_as(
+        app,
+        Some(&captain.token),
+        Method::PUT,
+        &format!("/team/by-id/{}/roster/{}", team_id, player_id),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+pub fn assert_problem(status: StatusCode, body: &Value, expected: StatusCode, code: &str) {
+    assert_eq!(status, expected, "{}", body);
+    assert_eq!(body["status"], expected.as_u16());
+    assert_eq!(body["code"], code);
+}
+
+// Sends the body as an uploaded .ics file
+pub async fn post_ics(app: &Router, token: &str, uri: &str, body: &str) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .header(header::CONTENT_TYPE, "text/calendar")
+        .body(Body::from(body.replace('\n', "\r\n")))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
@@ -0,0 +1,118 @@
+use std::path::PathBuf;
+
+use team_availablity_coordinator::config::{Config, ConfigArgs, ConfigError, StoreKind};
+
+fn write_config(name: &str, text: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("{}-{}.toml", name, std::process::id()));
+    std::fs::write(&path, text).unwrap();
+    path
+}
+
+fn invalid(args: &ConfigArgs) -> String {
+    match Config::load(args) {
+        Err(ConfigError::Invalid(message)) => message,
+        other => panic!("expected an invalid config, got {:?}", other),
+    }
+}
+
+#[test]
+fn flags_override_the_config_file() {
+    let path = write_config(
+        "layered",
+        r#"
+bind = "127.0.0.1:8080"
+shutdown_timeout_secs = 10
+store = "memory"
+
+[database]
+max_connections = 20
+idle_timeout_secs = 60
+skip_migrations = false
+
+[log]
+level = "debug"
+
+[cors]
+origins = ["https://app.example.com"]
+"#,
+    );
+    let args = ConfigArgs {
+        config_file: Some(path.clone()),
+        bind: Some("127.0.0.1:9090".parse().unwrap()),
+        db_max_connections: Some(8),
+        skip_migrations: true,
+        ..ConfigArgs::default()
+    };
+    let config = Config::load(&args).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(config.bind, "127.0.0.1:9090".parse().unwrap());
+    assert_eq!(config.shutdown_timeout_secs, 10);
+    assert_eq!(config.database.max_connections, 8);
+    assert_eq!(config.database.idle_timeout_secs, 60);
+    assert_eq!(config.database.acquire_timeout_secs, 30);
+    assert!(config.database.skip_migrations);
+    assert_eq!(config.store, StoreKind::Memory);
+    assert_eq!(config.log.level, "debug");
+    assert!(config.cors.layer().is_some());
+    assert!(config.database.url().is_err());
+}
+
+#[test]
+fn unknown_settings_are_rejected() {
+    let path = write_config("unknown", "[database]\nmax_conections = 20\n");
+    let result = Config::load(&ConfigArgs {
+        config_file: Some(path.clone()),
+        ..ConfigArgs::default()
+    });
+    std::fs::remove_file(path).unwrap();
+    let err = result.unwrap_err();
+    assert!(matches!(err, ConfigError::Parse { .. }), "{:?}", err);
+    assert!(err.to_string().contains("max_conections"), "{}", err);
+
+    let path = write_config("unknown-store", "store = \"sqlite\"\n");
+    let result = Config::load(&ConfigArgs {
+        config_file: Some(path.clone()),
+        ..ConfigArgs::default()
+    });
+    std::fs::remove_file(path).unwrap();
+    let err = result.unwrap_err();
+    assert!(matches!(err, ConfigError::Parse { .. }), "{:?}", err);
+    assert!(err.to_string().contains("sqlite"), "{}", err);
+}
+
+#[test]
+fn invalid_settings_are_reported() {
+    let defaults = Config::load(&ConfigArgs::default()).unwrap();
+    assert_eq!(defaults.bind, "0.0.0.0:3000".parse().unwrap());
+    assert_eq!(defaults.shutdown_timeout_secs, 30);
+    assert_eq!(defaults.store, StoreKind::Postgres);
+    assert!(!defaults.database.skip_migrations);
+    assert!(defaults.cors.layer().is_none());
+
+    let message = invalid(&ConfigArgs {
+        db_min_connections: Some(10),
+        ..ConfigArgs::default()
+    });
+    assert!(message.contains("min_connections"), "{}", message);
+
+    let message = invalid(&ConfigArgs {
+        log_level: Some("info,sqlx=loud".to_string()),
+        ..ConfigArgs::default()
+    });
+    assert!(message.contains("log.level"), "{}", message);
+
+    let message = invalid(&ConfigArgs {
+        static_dir: Some(PathBuf::from("does/not/exist")),
+        ..ConfigArgs::default()
+    });
+    assert!(message.contains("static.dir"), "{}", message);
+
+    for origins in [vec!["*", "https://app.example.com"], vec!["https://app.example.com/"]] {
+        let message = invalid(&ConfigArgs {
+            cors_origins: Some(origins.into_iter().map(String::from).collect()),
+            ..ConfigArgs::default()
+        });
+        assert!(message.contains("cors.origins"), "{}", message);
+    }
+}
@@ -0,0 +1,346 @@
+// The API against the Postgres store. `#[sqlx::test]` creates a fresh database per test
+// from DATABASE_URL and applies the migrations, the role needs CREATEDB.
+use std::sync::Arc;
+
+use axum::{
+    http::{Method, StatusCode},
+    Router,
+};
+use serde_json::json;
+use sqlx::PgPool;
+
+use team_availablity_coordinator::api::{api_routes, health_routes, DynAvailStore};
+use team_availablity_coordinator::data::{AvailablityStore, PostgresAvailablityStore};
+use team_availablity_coordinator::migrations;
+
+mod common;
+
+use common::*;
+
+fn app(pool: PgPool) -> Router {
+    let store = Arc::new(PostgresAvailablityStore::new(pool)) as DynAvailStore;
+    api_routes(store)
+}
+
+#[sqlx::test(migrator = "team_availablity_coordinator::migrations::MIGRATOR")]
+async fn available_blocks_round_trip(pool: PgPool) {
+    let app = app(pool);
+    let alice = create_player(&app, "alice").await;
+    let block = create_block(&app, &alice, alice.id, "18:00:00", "22:00:00", DAILY).await;
+    let id = block["id"].as_i64().unwrap();
+
+    let (status, fetched) =
+        get_as(&app, &alice.token, &format!("/available-blocks/by-id/{}", id)).await;
+    assert_eq!(status, StatusCode::OK, "{}", fetched);
+    assert_eq!(fetched["startTime"], "18:00:00");
+    assert_eq!(fetched["timezone"], "UTC");
+
+    let (status, content_type, calendar) = get_text_as(
+        &app,
+        &alice.token,
+        &format!("/available-blocks/by-player/{}/calendar.ics", alice.id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(content_type.starts_with("text/calendar"));
+    assert!(calendar.contains("DTSTART:20261019T180000Z"), "{}", calendar);
+
+    let (status, feed) =
+        post_as(&app, &alice.token, &format!("/player/{}/feed", alice.id), json!({})).await;
+    assert_eq!(status, StatusCode::OK, "{}", feed);
+    let feed_uri = format!("/feeds/player/{}.ics", feed["token"].as_str().unwrap());
+    let (status, _, subscribed) = get_text(&app, &feed_uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(subscribed.matches("BEGIN:VEVENT").count(), 1, "{}", subscribed);
+
+    let (status, _) = send_as(
+        &app,
+        Some(&alice.token),
+        Method::DELETE,
+        &format!("/available-blocks/by-id/{}", id),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, blocks) = get_as(
+        &app,
+        &alice.token,
+        &format!("/available-blocks/by-player/{}", alice.id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(blocks, json!([]));
+}
+
+#[sqlx::test(migrator = "team_availablity_coordinator::migrations::MIGRATOR")]
+async fn team_availability_and_events(pool: PgPool) {
+    let app = app(pool);
+    let alice = create_player(&app, "alice").await;
+    let bob = create_player(&app, "bob").await;
+    let team_id = create_team(&app, &alice, "falcons").await;
+    join_team(&app, &alice, team_id, bob.id).await;
+    create_block(&app, &alice, alice.id, "18:00:00", "22:00:00", DAILY).await;
+    create_block(&app, &bob, bob.id, "20:00:00", "23:00:00", DAILY).await;
+
+    let (status, body) = get_as(
+        &app,
+        &bob.token,
+        &format!(
+            "/team/by-id/{}/availability?from=2026-10-19T00:00:00Z&to=2026-10-20T00:00:00Z",
+            team_id
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(
+        body["allAvailable"],
+        json!([{ "start": "2026-10-19T20:00:00Z", "end": "2026-10-19T22:00:00Z" }])
+    );
+
+    let events_uri = format!("/team/by-id/{}/events", team_id);
+    let (status, event) = post_as(
+        &app,
+        &alice.token,
+        &events_uri,
+        json!({
+            "title": "Practice",
+            "start": "2026-10-19T19:00:00Z",
+            "end": "2026-10-19T21:00:00Z",
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", event);
+    assert_eq!(event["unavailable"][0]["player_id"], bob.id);
+
+    let (status, events) = send_as(&app, Some(&bob.token), Method::GET, &events_uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(events.as_array().unwrap().len(), 1);
+}
+
+// Rows written before the columns were checked can hold NULLs, they are reported instead of
+// failing the scan
+#[sqlx::test(migrator = "team_availablity_coordinator::migrations::MIGRATOR")]
+async fn null_block_columns_are_invalid_rows(pool: PgPool) {
+    let app = app(pool.clone());
+    let alice = create_player(&app, "alice").await;
+    let block = create_block(&app, &alice, alice.id, "18:00:00", "22:00:00", DAILY).await;
+    let null_repeats: i32 = sqlx::query_scalar(
+        "INSERT INTO available_blocks(start_time, end_time, repeats, player_id)
+        VALUES ('18:00', '20:00', NULL, $1) RETURNING id",
+    )
+    .bind(alice.id as i32)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    let null_start: i32 = sqlx::query_scalar(
+        "INSERT INTO available_blocks(start_time, end_time, repeats, player_id)
+        VALUES (NULL, '20:00', $1, $2) RETURNING id",
+    )
+    .bind(DAILY)
+    .bind(alice.id as i32)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    let store = PostgresAvailablityStore::new(pool);
+    store.set_admin(alice.user_id as i32, true).await.unwrap();
+    let (status, body) = send_as(
+        &app,
+        Some(&alice.token),
+        Method::GET,
+        "/admin/available-blocks/invalid",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(
+        body,
+        json!([
+            {
+                "id": null_repeats,
+                "playerId": alice.id,
+                "repeats": null,
+                "timezone": "UTC",
+                "error": "repeats is NULL",
+            },
+            {
+                "id": null_start,
+                "playerId": alice.id,
+                "repeats": DAILY,
+                "timezone": "UTC",
+                "error": "start_time is NULL",
+            },
+        ])
+    );
+
+    let uri = format!("/available-blocks/by-player/{}", alice.id);
+    let (status, blocks) = get_as(&app, &alice.token, &format!("{}?on_invalid=skip", uri)).await;
+    assert_eq!(status, StatusCode::OK, "{}", blocks);
+    assert_eq!(blocks.as_array().unwrap().len(), 1);
+    assert_eq!(blocks[0]["id"], block["id"]);
+    let (status, body) = get_as(&app, &alice.token, &uri).await;
+    assert_problem(status, &body, StatusCode::INTERNAL_SERVER_ERROR, "invalid_stored_data");
+}
+
+// One teammate's malformed block leaves that block out of the team's reads instead of failing them
+#[sqlx::test(migrator = "team_availablity_coordinator::migrations::MIGRATOR")]
+async fn team_reads_skip_invalid_blocks(pool: PgPool) {
+    let app = app(pool.clone());
+    let alice = create_player(&app, "alice").await;
+    let bob = create_player(&app, "bob").await;
+    let team_id = create_team(&app, &alice, "falcons").await;
+    join_team(&app, &alice, team_id, bob.id).await;
+    create_block(&app, &alice, alice.id, "18:00:00", "22:00:00", DAILY).await;
+    create_block(&app, &bob, bob.id, "20:00:00", "23:00:00", DAILY).await;
+    for repeats in [Some("not a rule"), None] {
+        sqlx::query(
+            "INSERT INTO available_blocks(start_time, end_time, repeats, player_id)
+            VALUES ('08:00', '12:00', $1, $2)",
+        )
+        .bind(repeats)
+        .bind(bob.id as i32)
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+
+    let window = "from=2026-10-19T00:00:00Z&to=2026-10-20T00:00:00Z";
+    let (status, body) = get_as(
+        &app,
+        &bob.token,
+        &format!("/team/by-id/{}/availability?{}", team_id, window),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(
+        body["allAvailable"],
+        json!([{ "start": "2026-10-19T20:00:00Z", "end": "2026-10-19T22:00:00Z" }])
+    );
+
+    let (status, body) = get_as(
+        &app,
+        &bob.token,
+        &format!("/team/by-id/{}/suggestions?{}", team_id, window),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body[0]["start"], "2026-10-19T20:00:00Z");
+
+    let (status, _, body) = get_text_as(
+        &app,
+        &bob.token,
+        &format!("/team/by-id/{}/freebusy.ics?{}", team_id, window),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body.matches("BEGIN:VFREEBUSY").count(), 2);
+
+    let (status, feed) = post_as(
+        &app,
+        &alice.token,
+        &format!("/team/by-id/{}/feed", team_id),
+        json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", feed);
+    let (status, _, body) = get_text(
+        &app,
+        &format!("/feeds/team/{}.ics", feed["token"].as_str().unwrap()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body.contains("BEGIN:VEVENT"), "{}", body);
+}
+
+// A malformed roster block can't turn a saved event into an error, a retry would add it twice
+#[sqlx::test(migrator = "team_availablity_coordinator::migrations::MIGRATOR")]
+async fn event_writes_skip_invalid_blocks(pool: PgPool) {
+    let app = app(pool.clone());
+    let alice = create_player(&app, "alice").await;
+    let bob = create_player(&app, "bob").await;
+    let team_id = create_team(&app, &alice, "falcons").await;
+    join_team(&app, &alice, team_id, bob.id).await;
+    create_block(&app, &alice, alice.id, "18:00:00", "22:00:00", DAILY).await;
+    sqlx::query(
+        "INSERT INTO available_blocks(start_time, end_time, repeats, player_id)
+        VALUES ('08:00', '12:00', 'not a rule', $1)",
+    )
+    .bind(bob.id as i32)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let events_uri = format!("/team/by-id/{}/events", team_id);
+    let (status, event) = post_as(
+        &app,
+        &alice.token,
+        &events_uri,
+        json!({
+            "title": "Practice",
+            "start": "2026-10-19T19:00:00Z",
+            "end": "2026-10-19T21:00:00Z",
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", event);
+    assert_eq!(event["unavailable"][0]["player_id"], bob.id);
+
+    let (status, updated) = send_as(
+        &app,
+        Some(&alice.token),
+        Method::PATCH,
+        &format!("{}/{}", events_uri, event["id"]),
+        Some(json!({
+            "title": "Practice",
+            "start": "2026-10-19T20:00:00Z",
+            "end": "2026-10-19T22:00:00Z",
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", updated);
+
+    let (status, events) = get_as(&app, &alice.token, &events_uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(events.as_array().unwrap().len(), 1);
+}
+
+// The probe is unauthenticated, the database error stays in the log
+#[sqlx::test(migrator = "team_availablity_coordinator::migrations::MIGRATOR")]
+async fn readiness_hides_the_database_error(pool: PgPool) {
+    let store = Arc::new(PostgresAvailablityStore::new(pool.clone())) as DynAvailStore;
+    let app = health_routes(store);
+    let (status, readiness) = send(&app, Method::GET, "/readyz", None).await;
+    assert_eq!(status, StatusCode::OK, "{}", readiness);
+
+    pool.close().await;
+    let (status, readiness) = send(&app, Method::GET, "/readyz", None).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE, "{}", readiness);
+    assert_eq!(readiness["ready"], false);
+    assert_eq!(readiness["error"], "database unavailable");
+}
+
+// Databases set up by hand before the migrations existed may already use the fixed column name
+#[sqlx::test(migrations = false)]
+async fn migrations_adopt_a_hand_built_schema(pool: PgPool) {
+    sqlx::raw_sql(
+        "CREATE TABLE users(id SERIAL PRIMARY KEY, name varchar(20) UNIQUE not null);
+        CREATE TABLE players(id SERIAL PRIMARY KEY, user_id int not null REFERENCES users(id));
+        CREATE TABLE available_blocks(
+            id SERIAL PRIMARY KEY,
+            start_time time,
+            end_time time,
+            need_warning boolean,
+            repeats varchar(50),
+            player_id int not null REFERENCES players(id)
+        );",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    migrations::up(&pool).await.unwrap();
+    let app = app(pool);
+    let alice = create_player(&app, "alice").await;
+    let block = create_block(&app, &alice, alice.id, "18:00:00", "22:00:00", DAILY).await;
+    assert_eq!(block["needWarning"], false);
+}